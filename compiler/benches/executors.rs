@@ -0,0 +1,116 @@
+// Compares the ways to run a compiled program: single steps, Machine::run with sweeps and CompiledMachine.
+// Run with `cargo bench -p turing_compiler --bench executors`, every program is timed RUNS times and the fastest run is shown.
+use std::time::{Duration, Instant};
+
+use turing_compiler::{compile_with_options, CompileOptions};
+use turing_vm::{CompiledMachine, Machine, RunOutcome};
+
+const RUNS: usize = 5;
+
+const PROGRAMS: [(&str, &str, u32); 4] = [
+    (
+        "while loop",
+        "
+let a = 4;
+let b = 2;
+while (a > 0) {
+    a = a - 1;
+    b = b * 2;
+};
+return b;",
+        8,
+    ),
+    (
+        "functions",
+        "
+fn add(a, b) {
+    return a + b;
+};
+fn main() {
+    let c = add(1,8);
+    let d = add(2,3);
+    let e = add(c,d);
+    return e;
+};",
+        8,
+    ),
+    (
+        "nested loops",
+        "
+let total = 0;
+let i = 0;
+while i < 40 {
+    let j = 0;
+    while j < i {
+        total = total + j;
+        j = j + 1;
+    };
+    i = i + 1;
+};
+return total;",
+        16,
+    ),
+    (
+        "array",
+        "
+let xs = [0; 20];
+let a = 0;
+let i = 0;
+while i < 20 {
+    xs[i] = i * i;
+    a = a + xs[i];
+    i = i + 1;
+};
+return a;",
+        32,
+    ),
+];
+
+fn main() {
+    println!(
+        "{:<14}{:>12}{:>14}{:>14}{:>14}",
+        "program", "steps", "step", "run", "compiled"
+    );
+
+    for (name, code, word_bits) in PROGRAMS {
+        let options = CompileOptions {
+            word_bits,
+            ..Default::default()
+        };
+        let program = compile_with_options(code, &options).turing_program;
+        let machine = Machine::load(&program).unwrap();
+
+        let (steps, step_time) = fastest(|| {
+            let mut machine = machine.clone();
+            while machine.step().is_none() {}
+            machine.steps()
+        });
+        let (_, run_time) = fastest(|| {
+            let mut machine = machine.clone();
+            assert_eq!(machine.run(), RunOutcome::Halted);
+            machine.steps()
+        });
+        let (_, compiled_time) = fastest(|| {
+            let mut compiled = CompiledMachine::new(machine.clone());
+            assert_eq!(compiled.run(), RunOutcome::Halted);
+            compiled.machine().steps()
+        });
+
+        println!(
+            "{name:<14}{steps:>12}{:>14?}{:>14?}{:>14?}",
+            step_time, run_time, compiled_time
+        );
+    }
+}
+
+// The result and the time of the fastest of RUNS runs
+fn fastest(run: impl Fn() -> u64) -> (u64, Duration) {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let steps = run();
+            (steps, start.elapsed())
+        })
+        .min_by_key(|&(_, time)| time)
+        .unwrap()
+}
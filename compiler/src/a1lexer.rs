@@ -1,3 +1,7 @@
+use std::{iter::Peekable, str::CharIndices};
+
+use crate::diagnostics::{Diagnostic, Span};
+
 #[derive(Debug, Clone)]
 pub enum TokenType {
     Keyword,
@@ -15,75 +19,199 @@ pub enum TokenType {
 pub struct Token {
     pub type_: TokenType,
     pub value: String,
+    pub span: Span,
+}
+
+// Operators made of two characters, checked before the single character operators
+const TWO_CHAR_OPERATORS: [(&str, TokenType); 8] = [
+    ("==", TokenType::ComparisonOperator),
+    (">=", TokenType::ComparisonOperator),
+    ("<=", TokenType::ComparisonOperator),
+    ("!=", TokenType::ComparisonOperator),
+    ("&&", TokenType::Operator),
+    ("||", TokenType::Operator),
+    ("<<", TokenType::Operator),
+    (">>", TokenType::Operator),
+];
+
+struct Scanner<'a> {
+    code: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+    column: usize,
+    tokens: Vec<Token>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// Scan the source code character by character.
+// Returns all tokens that could be recognized, and a diagnostic for every part that could not.
+pub fn lexer(code: String) -> (Vec<Token>, Vec<Diagnostic>) {
+    let mut scanner = Scanner {
+        code: &code,
+        chars: code.char_indices().peekable(),
+        line: 1,
+        column: 1,
+        tokens: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+
+    scanner.scan();
+
+    (scanner.tokens, scanner.diagnostics)
+}
+
+impl<'a> Scanner<'a> {
+    fn scan(&mut self) {
+        while let Some(&(start, c)) = self.chars.peek() {
+            let (line, column) = (self.line, self.column);
+
+            if c.is_whitespace() {
+                self.advance();
+            } else if self.code[start..].starts_with("//") {
+                // Line comment, skip until the end of the line
+                while self.chars.peek().is_some_and(|&(_, c)| c != '\n') {
+                    self.advance();
+                }
+            } else if self.code[start..].starts_with("/*") {
+                self.skip_block_comment(start, line, column);
+            } else if c.is_ascii_digit() {
+                self.scan_number(start, line, column);
+            } else if c.is_alphabetic() || c == '_' {
+                while self
+                    .chars
+                    .peek()
+                    .is_some_and(|&(_, c)| c.is_alphanumeric() || c == '_')
+                {
+                    self.advance();
+                }
+                let word = &self.code[start..self.offset()];
+                let type_ = match word {
+                    "let" | "return" | "while" | "fn" | "if" | "else" | "print" => {
+                        TokenType::Keyword
+                    }
+                    _ => TokenType::Identifier,
+                };
+                self.push(type_, start, line, column);
+            } else if let Some((operator, type_)) = TWO_CHAR_OPERATORS
+                .iter()
+                .find(|(operator, _)| self.code[start..].starts_with(operator))
+            {
+                self.advance();
+                self.advance();
+                let token = Token {
+                    type_: type_.clone(),
+                    value: operator.to_string(),
+                    span: Span::new(line, column, start, start + operator.len()),
+                };
+                self.tokens.push(token);
+            } else {
+                self.advance();
+                let type_ = match c {
+                    '=' | '-' | '+' | '*' | '/' | '%' | '!' | '&' | '|' | '^' | '~' => {
+                        TokenType::Operator
+                    }
+                    '>' | '<' => TokenType::ComparisonOperator,
+                    '(' | ')' => TokenType::Parenthesis,
+                    '{' | '}' | '[' | ']' => TokenType::Bracket,
+                    ';' => TokenType::Semicolon,
+                    ',' => TokenType::Comma,
+                    _ => {
+                        let span = Span::new(line, column, start, self.offset());
+                        self.diagnostics.push(
+                            Diagnostic::error(format!("Unexpected character '{c}'"))
+                                .with_span(span),
+                        );
+                        continue;
+                    }
+                };
+                self.push(type_, start, line, column);
+            }
+        }
+    }
+
+    // Skip a /* */ comment, they can span multiple lines but are not nested
+    fn skip_block_comment(&mut self, start: usize, line: usize, column: usize) {
+        self.advance();
+        self.advance();
+
+        loop {
+            let offset = self.offset();
+            if self.code[offset..].starts_with("*/") {
+                self.advance();
+                self.advance();
+                return;
+            }
+            if self.advance().is_none() {
+                let span = Span::new(line, column, start, start + 2);
+                self.diagnostics
+                    .push(Diagnostic::error("Unterminated block comment").with_span(span));
+                return;
+            }
+        }
+    }
+
+    // Decimal, hexadecimal (0x1F) or binary (0b1010) number
+    fn scan_number(&mut self, start: usize, line: usize, column: usize) {
+        while self
+            .chars
+            .peek()
+            .is_some_and(|&(_, c)| c.is_alphanumeric() || c == '_')
+        {
+            self.advance();
+        }
+
+        let literal = &self.code[start..self.offset()];
+        if parse_number_literal(literal).is_none() {
+            let span = Span::new(line, column, start, self.offset());
+            self.diagnostics.push(
+                Diagnostic::error(format!("Invalid number literal '{literal}'")).with_span(span),
+            );
+            return;
+        }
+
+        self.push(TokenType::Number, start, line, column);
+    }
+
+    fn push(&mut self, type_: TokenType, start: usize, line: usize, column: usize) {
+        let end = self.offset();
+        self.tokens.push(Token {
+            type_,
+            value: self.code[start..end].to_string(),
+            span: Span::new(line, column, start, end),
+        });
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let (_, c) = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    // Byte offset of the next character
+    fn offset(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map(|&(offset, _)| offset)
+            .unwrap_or(self.code.len())
+    }
 }
 
-pub fn lexer(mut code: String) -> Vec<Token> {
-    // preprocess the code to add spaces around the parenthesis
-    code = code.replace("(", " ( ").replace(")", " ) ");
-    code = code.replace(";", " ; ");
-    code = code.replace(",", " , ");
-
-    // For simplicity, consider each line as a single statement and split it by spaces.
-    code.lines()
-        .flat_map(|line| {
-            line.split_whitespace()
-                .map(|word| match word {
-                    "let" => Token {
-                        type_: TokenType::Keyword,
-                        value: word.to_string(),
-                    },
-                    "return" => Token {
-                        type_: TokenType::Keyword,
-                        value: word.to_string(),
-                    },
-                    "while" => Token {
-                        type_: TokenType::Keyword,
-                        value: word.to_string(),
-                    },
-                    "fn" => Token {
-                        type_: TokenType::Keyword,
-                        value: word.to_string(),
-                    },
-                    "," => Token {
-                        type_: TokenType::Comma,
-                        value: word.to_string(),
-                    },
-                    "if" | "else" => Token {
-                        type_: TokenType::Keyword,
-                        value: word.to_string(),
-                    },
-                    "=" | "-" | "+" | "*" => Token {
-                        type_: TokenType::Operator,
-                        value: word.to_string(),
-                    },
-                    "(" | ")" => Token {
-                        type_: TokenType::Parenthesis,
-                        value: word.to_string(),
-                    },
-                    ";" => Token {
-                        type_: TokenType::Semicolon,
-                        value: word.to_string(),
-                    },
-                    "==" | ">" | "<" => Token {
-                        type_: TokenType::ComparisonOperator,
-                        value: word.to_string(),
-                    },
-                    "{" | "}" => Token {
-                        type_: TokenType::Bracket,
-                        value: word.to_string(),
-                    },
-                    _ if word.parse::<u8>().is_ok() => Token {
-                        type_: TokenType::Number,
-                        value: word.to_string(),
-                    },
-                    _ => Token {
-                        type_: TokenType::Identifier,
-                        value: word.to_string(),
-                    },
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect()
+// Get the value of a number literal such as 12, 0x1F or 0b1010
+pub fn parse_number_literal(literal: &str) -> Option<u64> {
+    if let Some(hex) = literal.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = literal.strip_prefix("0b") {
+        u64::from_str_radix(binary, 2).ok()
+    } else if literal.chars().all(|c| c.is_ascii_digit()) {
+        literal.parse::<u64>().ok()
+    } else {
+        None
+    }
 }
 
 pub fn tokens_to_string(tokens: Vec<Token>) -> String {
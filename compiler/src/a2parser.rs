@@ -2,7 +2,8 @@ use std::iter::Peekable;
 
 use serde::Serialize;
 
-use crate::a1lexer::Token;
+use crate::a1lexer::{parse_number_literal, Token, TokenType};
+use crate::diagnostics::{Diagnostic, Span};
 
 // pub struct AstNode {
 //     pub operator: Option<String>,
@@ -15,15 +16,25 @@ use crate::a1lexer::Token;
 pub enum AstNode {
     Variable {
         name: String,
+        #[serde(skip)]
+        span: Span,
     },
     Constant {
         value: String,
+        #[serde(skip)]
+        span: Span,
     },
     BinaryOperation {
         operator: String,
         left: Box<AstNode>,
         right: Box<AstNode>,
     },
+    UnaryOperation {
+        operator: String,
+        operand: Box<AstNode>,
+        #[serde(skip)]
+        span: Span,
+    },
     Conditional {
         condition: Box<AstNode>,
         then_branch: Box<AstNode>,
@@ -32,13 +43,42 @@ pub enum AstNode {
     Assignment {
         var_name: String,
         value: Box<AstNode>,
+        #[serde(skip)]
+        span: Span,
     },
     Declaration {
         var_name: String,
         value: Box<AstNode>,
+        #[serde(skip)]
+        span: Span,
+    },
+    ArrayDeclaration {
+        var_name: String,
+        value: Box<AstNode>, // every element starts with this value
+        size: usize,
+        #[serde(skip)]
+        span: Span,
+    },
+    Index {
+        name: String,
+        index: Box<AstNode>,
+        #[serde(skip)]
+        span: Span,
+    },
+    IndexAssignment {
+        var_name: String,
+        index: Box<AstNode>,
+        value: Box<AstNode>,
+        #[serde(skip)]
+        span: Span,
     },
     Return {
         value: Box<AstNode>,
+        #[serde(skip)]
+        span: Span,
+    },
+    Print {
+        value: Box<AstNode>,
     },
     While {
         condition: Box<AstNode>,
@@ -48,151 +88,208 @@ pub enum AstNode {
         name: String,
         args: Box<AstNode>,
         body: Box<AstNode>,
+        #[serde(skip)]
+        span: Span,
     },
     FunctionCall {
         name: String,
         args: Box<AstNode>,
+        #[serde(skip)]
+        span: Span,
     },
     Body(Vec<AstNode>),
     Arguments(Vec<AstNode>),
     CallArguments(Vec<AstNode>),
 }
 
-pub fn parser(tokens: Vec<Token>) -> AstNode {
+pub fn parser(tokens: Vec<Token>) -> Result<AstNode, Diagnostic> {
+    // Errors about running out of tokens point just past the last token
+    let end_of_input = tokens
+        .last()
+        .map(|token| {
+            let column = token.span.column + token.value.chars().count();
+            Span::new(token.span.line, column, token.span.end, token.span.end)
+        })
+        .unwrap_or(Span::new(1, 1, 0, 0));
+
     let mut iter = tokens.into_iter().peekable();
     let mut ast_nodes = Vec::new();
 
     while iter.peek().is_some() {
-        ast_nodes.push(parse_statement(&mut iter));
+        match parse_statement(&mut iter) {
+            Ok(node) => ast_nodes.push(node),
+            Err(diagnostic) if diagnostic.span.is_none() => {
+                return Err(diagnostic.with_span(end_of_input))
+            }
+            Err(diagnostic) => return Err(diagnostic),
+        }
     }
 
-    AstNode::Body(ast_nodes)
+    Ok(AstNode::Body(ast_nodes))
 }
 
-fn parse_body<I>(iter: &mut Peekable<I>) -> AstNode
+// Get the next token, or an "Unexpected end of tokens" error without a span
+fn next_token<I>(iter: &mut Peekable<I>) -> Result<Token, Diagnostic>
+where
+    I: Iterator<Item = Token>,
+{
+    iter.next()
+        .ok_or_else(|| Diagnostic::error("Unexpected end of tokens"))
+}
+
+// Consume the next token and check that it has the expected value
+fn expect<I>(iter: &mut Peekable<I>, value: &str) -> Result<Token, Diagnostic>
+where
+    I: Iterator<Item = Token>,
+{
+    let token = next_token(iter)?;
+    if token.value != value {
+        return Err(
+            Diagnostic::error(format!("Expected '{}' but found '{}'", value, token.value))
+                .with_span(token.span),
+        );
+    }
+    Ok(token)
+}
+
+fn parse_body<I>(iter: &mut Peekable<I>) -> Result<AstNode, Diagnostic>
 where
     I: Iterator<Item = Token>,
 {
     let mut nodes = Vec::new();
 
-    iter.next(); // consume opening bracket
+    expect(iter, "{")?; // consume opening bracket
 
-    while let Some(token) = iter.peek() {
+    loop {
+        let token = iter
+            .peek()
+            .ok_or_else(|| Diagnostic::error("Expected '}' at the end of body"))?;
         match token.value.as_str() {
             "}" => {
                 iter.next(); // consume closing bracket
                 break;
             }
-            _ => nodes.push(parse_statement(iter)),
+            _ => nodes.push(parse_statement(iter)?),
         }
     }
 
-    AstNode::Body(nodes)
+    Ok(AstNode::Body(nodes))
 }
 
-fn parse_statement<I>(iter: &mut Peekable<I>) -> AstNode
+fn parse_statement<I>(iter: &mut Peekable<I>) -> Result<AstNode, Diagnostic>
 where
     I: Iterator<Item = Token>,
 {
-    let node = if let Some(token) = iter.peek() {
-        match token.value.as_str() {
-            "let" => {
-                iter.next(); // consume "let"
-                let var_name = iter.next().unwrap().value.clone(); // get variable name
-                assert_eq!(iter.next().unwrap().value, "="); // consume "="
-                let right = parse_expression(iter); // parse the right-hand side
-                AstNode::Declaration {
-                    value: Box::new(right),
-                    var_name,
+    let token = iter
+        .peek()
+        .ok_or_else(|| Diagnostic::error("Unexpected end of tokens"))?;
+
+    let node = match token.value.as_str() {
+        "let" => {
+            iter.next(); // consume "let"
+            let var_name = parse_identifier(iter)?; // get variable name
+            expect(iter, "=")?; // consume "="
+            match iter.peek() {
+                Some(token) if token.value == "[" => {
+                    let (value, size) = parse_array(iter)?; // parse [value; size]
+                    AstNode::ArrayDeclaration {
+                        value: Box::new(value),
+                        size,
+                        var_name: var_name.value,
+                        span: var_name.span,
+                    }
                 }
-            }
-            "if" => {
-                iter.next(); // consume "if"
-                let condition = parse_expression(iter); // parse the condition
-                assert_eq!(iter.next().unwrap().value, "{"); // consume opening bracket
-                let then_branch = parse_statement(iter);
-                assert_eq!(iter.next().unwrap().value, "}"); // consume closing bracket
-
-                // Check if there is an "else" branch
-                if let Some(token) = iter.peek() {
-                    if token.value == "else" {
-                        iter.next(); // consume "else"
-                        assert_eq!(iter.next().unwrap().value, "{"); // consume opening bracket
-                        let else_branch = parse_statement(iter);
-                        assert_eq!(iter.next().unwrap().value, "}"); // consume closing bracket
-
-                        AstNode::Conditional {
-                            condition: Box::new(condition),
-                            then_branch: Box::new(then_branch),
-                            else_branch: Some(Box::new(else_branch)),
-                        }
-                    } else {
-                        AstNode::Conditional {
-                            condition: Box::new(condition),
-                            then_branch: Box::new(then_branch),
-                            else_branch: None,
-                        }
+                _ => {
+                    let right = parse_expression(iter)?; // parse the right-hand side
+                    AstNode::Declaration {
+                        value: Box::new(right),
+                        var_name: var_name.value,
+                        span: var_name.span,
                     }
-                } else {
-                    panic!("Unexpected end of tokens");
                 }
             }
-            "while" => {
-                iter.next(); // consumes "while"
-                println!("parse_statement: {:?}", iter.peek());
-                let condition = parse_expression(iter); // parse the condition
-                println!("condition: {:?}", condition);
-                let body = parse_body(iter); // parse the body
-                println!("body: {:?}", body);
-                AstNode::While {
-                    condition: Box::new(condition),
-                    body: Box::new(body),
-                }
+        }
+        "if" => parse_conditional(iter)?,
+        "while" => {
+            iter.next(); // consumes "while"
+            let condition = parse_expression(iter)?; // parse the condition
+            let body = parse_body(iter)?; // parse the body
+            AstNode::While {
+                condition: Box::new(condition),
+                body: Box::new(body),
             }
-            "fn" => {
-                iter.next(); // consume "fn"
-                let var_name = iter.next().unwrap().value.clone(); // get function name
-                assert_eq!(iter.next().unwrap().value, "("); // consume opening parenthesis
-                let args = parse_arguments(iter); // parse the arguments
-
-                let body = parse_body(iter); // parse the body
-                AstNode::Function {
-                    name: var_name,
-                    args: Box::new(args),
-                    body: Box::new(body),
-                }
+        }
+        "fn" => {
+            iter.next(); // consume "fn"
+            let var_name = parse_identifier(iter)?; // get function name
+            expect(iter, "(")?; // consume opening parenthesis
+            let args = parse_arguments(iter)?; // parse the arguments
+
+            let body = parse_body(iter)?; // parse the body
+            AstNode::Function {
+                name: var_name.value,
+                args: Box::new(args),
+                body: Box::new(body),
+                span: var_name.span,
             }
-            "return" => {
-                iter.next(); // consume "return"
-                let value = parse_expression(iter); // parse the return value
-                AstNode::Return {
-                    value: Box::new(value),
-                }
+        }
+        "return" => {
+            let span = next_token(iter)?.span; // consume "return"
+            let value = parse_expression(iter)?; // parse the return value
+            AstNode::Return {
+                value: Box::new(value),
+                span,
             }
-            _ => {
-                let var_name = iter.next().unwrap().value.clone(); // get variable name
-                if iter.peek().unwrap().value == "=" {
-                    iter.next(); // consume "="
-                    let right = parse_expression(iter); // parse the right-hand side
-                    AstNode::Assignment {
-                        value: Box::new(right),
-                        var_name,
-                    }
-                } else if iter.peek().unwrap().value == "(" {
-                    iter.next(); // consume "("
-                    let args = parse_call_arguments(iter); // parse the arguments
-                    AstNode::FunctionCall {
-                        name: var_name,
-                        args: Box::new(args),
-                    }
-                } else {
-                    println!("Unexpected token: {:?}", iter.peek());
-                    parse_expression(iter)
+        }
+        "print" => {
+            iter.next(); // consume "print"
+            let value = parse_expression(iter)?; // parse the printed value
+            AstNode::Print {
+                value: Box::new(value),
+            }
+        }
+        _ => {
+            let identifier = parse_identifier(iter)?; // get variable name
+            let var_name = identifier.value;
+            let token = iter
+                .peek()
+                .ok_or_else(|| Diagnostic::error("Unexpected end of tokens"))?;
+            if token.value == "=" {
+                iter.next(); // consume "="
+                let right = parse_expression(iter)?; // parse the right-hand side
+                AstNode::Assignment {
+                    value: Box::new(right),
+                    var_name,
+                    span: identifier.span,
+                }
+            } else if token.value == "[" {
+                iter.next(); // consume "["
+                let index = parse_expression(iter)?; // parse the index
+                expect(iter, "]")?; // consume "]"
+                expect(iter, "=")?; // consume "="
+                let right = parse_expression(iter)?; // parse the right-hand side
+                AstNode::IndexAssignment {
+                    index: Box::new(index),
+                    value: Box::new(right),
+                    var_name,
+                    span: identifier.span,
+                }
+            } else if token.value == "(" {
+                iter.next(); // consume "("
+                let args = parse_call_arguments(iter)?; // parse the arguments
+                AstNode::FunctionCall {
+                    name: var_name,
+                    args: Box::new(args),
+                    span: identifier.span,
                 }
+            } else {
+                return Err(Diagnostic::error(format!(
+                    "Unexpected token '{}' after '{}'",
+                    token.value, var_name
+                ))
+                .with_span(token.span));
             }
         }
-    } else {
-        panic!("Unexpected end of tokens");
     };
 
     // Consume the semicolon at the end of the statement
@@ -200,21 +297,103 @@ where
         Some(token) if token.value == ";" => {
             iter.next();
         }
+        Some(token) => {
+            return Err(Diagnostic::error(format!(
+                "Expected semicolon at the end of statement, found '{}'",
+                token.value
+            ))
+            .with_span(token.span));
+        }
+        None => {
+            return Err(Diagnostic::error(
+                "Expected semicolon at the end of statement",
+            ));
+        }
+    };
+
+    Ok(node)
+}
+
+// Parse an if statement, the else branch can be a body or another if statement
+fn parse_conditional<I>(iter: &mut Peekable<I>) -> Result<AstNode, Diagnostic>
+where
+    I: Iterator<Item = Token>,
+{
+    expect(iter, "if")?; // consume "if"
+    let condition = parse_expression(iter)?; // parse the condition
+    let then_branch = parse_body(iter)?; // parse the then body
+
+    // Check if there is an "else" branch, "else if" is an else body with a single conditional
+    let else_branch = match iter.peek() {
+        Some(token) if token.value == "else" => {
+            iter.next(); // consume "else"
+            match iter.peek() {
+                Some(token) if token.value == "if" => {
+                    Some(Box::new(AstNode::Body(vec![parse_conditional(iter)?])))
+                }
+                _ => Some(Box::new(parse_body(iter)?)),
+            }
+        }
+        _ => None,
+    };
+
+    Ok(AstNode::Conditional {
+        condition: Box::new(condition),
+        then_branch: Box::new(then_branch),
+        else_branch,
+    })
+}
+
+// Parse an array of equal elements, such as [0; 8]
+fn parse_array<I>(iter: &mut Peekable<I>) -> Result<(AstNode, usize), Diagnostic>
+where
+    I: Iterator<Item = Token>,
+{
+    expect(iter, "[")?; // consume "["
+    let value = parse_expression(iter)?; // parse the value of every element
+    expect(iter, ";")?; // consume ";"
+
+    let token = next_token(iter)?;
+    let size = match token.type_ {
+        TokenType::Number => parse_constant(&token)?,
         _ => {
-            panic!("Expected semicolon at the end of statement: {:?}", node);
+            return Err(Diagnostic::error(format!(
+                "Expected the array size but found '{}'",
+                token.value
+            ))
+            .with_span(token.span))
         }
     };
+    expect(iter, "]")?; // consume "]"
+
+    Ok((value, size as usize))
+}
 
-    node
+// Consume an identifier, such as a variable or function name
+fn parse_identifier<I>(iter: &mut Peekable<I>) -> Result<Token, Diagnostic>
+where
+    I: Iterator<Item = Token>,
+{
+    let token = next_token(iter)?;
+    match token.type_ {
+        TokenType::Identifier => Ok(token),
+        _ => Err(
+            Diagnostic::error(format!("Expected identifier but found '{}'", token.value))
+                .with_span(token.span),
+        ),
+    }
 }
 
-fn parse_arguments<I>(iter: &mut Peekable<I>) -> AstNode
+fn parse_arguments<I>(iter: &mut Peekable<I>) -> Result<AstNode, Diagnostic>
 where
     I: Iterator<Item = Token>,
 {
     let mut args = Vec::new();
 
-    while let Some(token) = iter.peek() {
+    loop {
+        let token = iter
+            .peek()
+            .ok_or_else(|| Diagnostic::error("Expected ')' at the end of arguments"))?;
         match token.value.as_str() {
             ")" => {
                 iter.next(); // consume closing parenthesis
@@ -224,25 +403,31 @@ where
                 iter.next(); // consume comma
             }
             _ => {
-                args.push(iter.next().unwrap().value.clone());
+                args.push(parse_identifier(iter)?);
             }
         }
     }
 
-    AstNode::Arguments(
+    Ok(AstNode::Arguments(
         args.into_iter()
-            .map(|arg| AstNode::Variable { name: arg })
+            .map(|arg| AstNode::Variable {
+                name: arg.value,
+                span: arg.span,
+            })
             .collect(),
-    )
+    ))
 }
 
-fn parse_call_arguments<I>(iter: &mut Peekable<I>) -> AstNode
+fn parse_call_arguments<I>(iter: &mut Peekable<I>) -> Result<AstNode, Diagnostic>
 where
     I: Iterator<Item = Token>,
 {
     let mut args = Vec::new();
 
-    while let Some(token) = iter.peek() {
+    loop {
+        let token = iter
+            .peek()
+            .ok_or_else(|| Diagnostic::error("Expected ')' at the end of arguments"))?;
         match token.value.as_str() {
             ")" => {
                 iter.next(); // consume closing parenthesis
@@ -252,95 +437,150 @@ where
                 iter.next(); // consume comma
             }
             _ => {
-                args.push(parse_expression(iter));
+                args.push(parse_expression(iter)?);
             }
         }
     }
 
-    AstNode::CallArguments(args)
+    Ok(AstNode::CallArguments(args))
 }
 
-fn parse_expression<I>(iter: &mut Peekable<I>) -> AstNode
+// Binary operators from weakest to strongest binding, operators on the same level are left associative.
+// Comparisons bind weaker than arithmetic and bitwise operators, so `a + 1 > b & 3` compares a + 1 with b & 3.
+const BINARY_OPERATORS: [&[&str]; 9] = [
+    &["||"],
+    &["&&"],
+    &["==", "!=", ">", ">=", "<", "<="],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+fn parse_expression<I>(iter: &mut Peekable<I>) -> Result<AstNode, Diagnostic>
 where
     I: Iterator<Item = Token>,
 {
-    let mut node = parse_term(iter);
-
-    println!("parse_expression: {:?}", node);
-
-    while let Some(token) = iter.peek() {
-        println!("parse_expression token: {:?}", token);
-        match token.value.as_str() {
-            "+" | "-" | "==" | ">" | "<" => {
-                let op = iter.next().unwrap().value.clone();
-                let right = parse_term(iter);
-                node = AstNode::BinaryOperation {
-                    operator: op,
-                    left: Box::new(node),
-                    right: Box::new(right),
-                }
-            }
-            "(" => {
-                if let AstNode::Variable { name } = node {
-                    iter.next(); // consume "("
-                    let args = parse_call_arguments(iter);
-                    node = AstNode::FunctionCall {
-                        name: name,
-                        args: Box::new(args),
-                    };
-                }
-            }
-            _ => break,
-        }
-    }
-
-    node
+    parse_binary_operation(iter, 0)
 }
 
-fn parse_term<I>(iter: &mut Peekable<I>) -> AstNode
+// Parse operators of the given level in BINARY_OPERATORS, and everything that binds stronger
+fn parse_binary_operation<I>(iter: &mut Peekable<I>, level: usize) -> Result<AstNode, Diagnostic>
 where
     I: Iterator<Item = Token>,
 {
-    let mut node = parse_factor(iter);
+    let Some(operators) = BINARY_OPERATORS.get(level) else {
+        return parse_factor(iter);
+    };
+
+    let mut node = parse_binary_operation(iter, level + 1)?;
 
     while let Some(token) = iter.peek() {
-        match token.value.as_str() {
-            "*" | "/" => {
-                let op = iter.next().unwrap().value.clone();
-                let right = parse_factor(iter);
-                node = AstNode::BinaryOperation {
-                    operator: op,
-                    left: Box::new(node),
-                    right: Box::new(right),
-                }
-            }
-            _ => break,
+        if !operators.contains(&token.value.as_str()) {
+            break;
+        }
+        let op = next_token(iter)?.value;
+        let right = parse_binary_operation(iter, level + 1)?;
+        node = AstNode::BinaryOperation {
+            operator: op,
+            left: Box::new(node),
+            right: Box::new(right),
         }
     }
 
-    node
+    Ok(node)
 }
 
-fn parse_factor<I>(iter: &mut Peekable<I>) -> AstNode
+fn parse_factor<I>(iter: &mut Peekable<I>) -> Result<AstNode, Diagnostic>
 where
     I: Iterator<Item = Token>,
 {
-    if let Some(token) = iter.next() {
-        match token.value.as_str() {
-            "(" => {
-                let node = parse_expression(iter);
-                assert_eq!(iter.next().unwrap().value, ")");
-                node
+    let token = next_token(iter)?;
+    match token.type_ {
+        TokenType::Parenthesis if token.value == "(" => {
+            let node = parse_expression(iter)?;
+            expect(iter, ")")?;
+            Ok(node)
+        }
+        TokenType::Operator if token.value == "!" || token.value == "~" => {
+            let operand = parse_factor(iter)?;
+            Ok(AstNode::UnaryOperation {
+                operator: token.value,
+                operand: Box::new(operand),
+                span: token.span,
+            })
+        }
+        TokenType::Operator if token.value == "-" => match iter.peek() {
+            // A minus sign directly in front of a number is a negative constant
+            Some(next) if matches!(next.type_, TokenType::Number) => {
+                let number = next_token(iter)?;
+                let value = parse_constant(&number)?;
+                let span = Span::new(
+                    token.span.line,
+                    token.span.column,
+                    token.span.start,
+                    number.span.end,
+                );
+                Ok(AstNode::Constant {
+                    value: format!("-{value}"),
+                    span,
+                })
             }
-            num if num.parse::<u8>().is_ok() => AstNode::Constant {
-                value: num.to_string(),
-            },
-            id if id.parse::<String>().is_ok() => AstNode::Variable {
-                name: id.to_string(),
-            },
-            _ => panic!("Unexpected token in parse_factor: {:?}", token),
+            _ => {
+                let operand = parse_factor(iter)?;
+                Ok(AstNode::UnaryOperation {
+                    operator: token.value,
+                    operand: Box::new(operand),
+                    span: token.span,
+                })
+            }
+        },
+        TokenType::Number => {
+            let value = parse_constant(&token)?;
+            Ok(AstNode::Constant {
+                value: value.to_string(),
+                span: token.span,
+            })
         }
-    } else {
-        panic!("Unexpected end of tokens");
+        TokenType::Identifier => match iter.peek() {
+            // An identifier followed by "[" is an element of an array
+            Some(next) if next.value == "[" => {
+                iter.next(); // consume "["
+                let index = parse_expression(iter)?;
+                expect(iter, "]")?;
+                Ok(AstNode::Index {
+                    name: token.value,
+                    index: Box::new(index),
+                    span: token.span,
+                })
+            }
+            // An identifier followed by "(" is a function call
+            Some(next) if next.value == "(" => {
+                iter.next(); // consume "("
+                let args = parse_call_arguments(iter)?;
+                Ok(AstNode::FunctionCall {
+                    name: token.value,
+                    args: Box::new(args),
+                    span: token.span,
+                })
+            }
+            _ => Ok(AstNode::Variable {
+                name: token.value,
+                span: token.span,
+            }),
+        },
+        _ => Err(
+            Diagnostic::error(format!("Unexpected token '{}' in expression", token.value))
+                .with_span(token.span),
+        ),
     }
 }
+
+// Constants are always stored in decimal
+fn parse_constant(token: &Token) -> Result<u64, Diagnostic> {
+    parse_number_literal(&token.value).ok_or_else(|| {
+        Diagnostic::error(format!("Invalid number literal '{}'", token.value)).with_span(token.span)
+    })
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use crate::a2parser::AstNode;
+use crate::diagnostics::{Diagnostic, Span};
+use crate::options::CompileOptions;
+use crate::symbol_table::SymbolTable;
+
+struct Analyzer {
+    functions: HashMap<String, usize>, // <function name, number of parameters>
+    arrays: HashMap<String, usize>,    // <storage name, number of elements>
+    symbols: SymbolTable,
+    in_function: bool,
+    has_main: bool,
+    options: CompileOptions,
+}
+
+// Check the AST for mistakes the parser can not see, such as calls to undefined functions.
+// Returns all errors and warnings, code generation should only continue if there are no errors.
+// Also returns the storage name of every variable, by the start of its name in the source code.
+pub fn analyze(
+    ast: &AstNode,
+    options: &CompileOptions,
+) -> (Vec<Diagnostic>, HashMap<usize, String>) {
+    let mut analyzer = Analyzer {
+        functions: HashMap::new(),
+        arrays: HashMap::new(),
+        symbols: SymbolTable::new(),
+        in_function: false,
+        has_main: false,
+        options: *options,
+    };
+
+    // Functions can be called before they are defined, so collect them first
+    analyzer.collect_functions(ast);
+    analyzer.has_main = analyzer.functions.contains_key("main");
+    analyzer.visit(ast);
+
+    (analyzer.symbols.diagnostics, analyzer.symbols.names)
+}
+
+impl Analyzer {
+    fn collect_functions(&mut self, node: &AstNode) {
+        match node {
+            AstNode::Body(nodes) => {
+                for node in nodes {
+                    self.collect_functions(node);
+                }
+            }
+            AstNode::Function {
+                name, args, span, ..
+            } => {
+                let params = match &**args {
+                    AstNode::Arguments(params) => params.len(),
+                    _ => 0,
+                };
+                if self.functions.insert(name.clone(), params).is_some() {
+                    self.error(format!("Function '{name}' is already defined"), *span);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn visit(&mut self, node: &AstNode) {
+        match node {
+            AstNode::Body(nodes) | AstNode::CallArguments(nodes) => {
+                for node in nodes {
+                    self.visit(node);
+                }
+            }
+            AstNode::Declaration {
+                var_name,
+                value,
+                span,
+            } => {
+                self.visit(value);
+                self.symbols.declare(var_name, *span);
+            }
+            AstNode::ArrayDeclaration {
+                var_name,
+                value,
+                size,
+                span,
+            } => {
+                self.visit(value);
+                if *size == 0 {
+                    self.error(
+                        format!("Array '{var_name}' must have at least one element"),
+                        *span,
+                    );
+                }
+                let storage_name = self.symbols.declare(var_name, *span);
+                self.arrays.insert(storage_name, *size);
+            }
+            AstNode::Assignment {
+                var_name,
+                value,
+                span,
+            } => {
+                self.visit(value);
+                self.resolve_scalar(var_name, *span);
+            }
+            AstNode::Variable { name, span } => {
+                self.resolve_scalar(name, *span);
+            }
+            AstNode::Index { name, index, span }
+            | AstNode::IndexAssignment {
+                var_name: name,
+                index,
+                span,
+                ..
+            } => {
+                if let AstNode::IndexAssignment { value, .. } = node {
+                    self.visit(value);
+                }
+                self.visit(index);
+                self.check_index(name, index, *span);
+            }
+            AstNode::Constant { value, span } => {
+                let (min_value, max_value) = self.options.value_range();
+                if !value
+                    .parse::<i128>()
+                    .is_ok_and(|value| (min_value..=max_value).contains(&value))
+                {
+                    let word_bits = self.options.word_bits;
+                    self.error(
+                        format!(
+                            "Constant {value} does not fit in {word_bits} bits ({min_value} to {max_value})"
+                        ),
+                        *span,
+                    );
+                }
+            }
+            AstNode::BinaryOperation { left, right, .. } => {
+                self.visit(left);
+                self.visit(right);
+            }
+            AstNode::UnaryOperation {
+                operator,
+                operand,
+                span,
+            } => {
+                // Without negative numbers -x is 2^bits - x
+                if operator == "-" && !self.options.signed {
+                    self.symbols.diagnostics.push(
+                        Diagnostic::warning(format!(
+                            "Negation of an unsigned number wraps around to 2^{} - x",
+                            self.options.word_bits
+                        ))
+                        .with_span(*span),
+                    );
+                }
+                self.visit(operand);
+            }
+            AstNode::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.visit(condition);
+                self.visit_block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.visit_block(else_branch);
+                }
+            }
+            AstNode::While { condition, body } => {
+                self.visit(condition);
+                self.visit_block(body);
+            }
+            AstNode::Return { value, span } => {
+                // Without a main function the top level code is the program, and its return value is the result
+                if !self.in_function && self.has_main {
+                    self.error(
+                        "Return outside of a function, the program starts at main",
+                        *span,
+                    );
+                }
+                self.visit(value);
+            }
+            AstNode::Print { value } => self.visit(value),
+            AstNode::Function {
+                name,
+                args,
+                body,
+                span,
+            } => {
+                if self.in_function {
+                    self.error(
+                        format!("Function '{name}' can not be defined inside another function"),
+                        *span,
+                    );
+                    return;
+                }
+
+                self.in_function = true;
+                self.symbols.enter_function(name);
+                if let AstNode::Arguments(params) = &**args {
+                    for param in params {
+                        if let AstNode::Variable { name, span } = param {
+                            self.symbols.declare(name, *span);
+                        }
+                    }
+                }
+                self.visit(body);
+                self.symbols.exit_function();
+                self.in_function = false;
+            }
+            AstNode::FunctionCall { name, args, span } => {
+                let arg_count = match &**args {
+                    AstNode::CallArguments(args) => args.len(),
+                    _ => 0,
+                };
+                match self.functions.get(name) {
+                    None => self.error(format!("Call to undefined function '{name}'"), *span),
+                    Some(&params) if params != arg_count => self.error(
+                        format!(
+                            "Function '{name}' expects {params} arguments, but got {arg_count}"
+                        ),
+                        *span,
+                    ),
+                    _ => {}
+                }
+                self.visit(args);
+            }
+            AstNode::Arguments(_) => {}
+        }
+    }
+
+    // Arrays can only be used through an index
+    fn resolve_scalar(&mut self, name: &str, span: Span) {
+        let storage_name = self.symbols.resolve(name, span);
+        if self.arrays.contains_key(&storage_name) {
+            self.error(
+                format!("Array '{name}' can only be used with an index"),
+                span,
+            );
+        }
+    }
+
+    // Indexes that are constants are checked against the size of the array
+    fn check_index(&mut self, name: &str, index: &AstNode, span: Span) {
+        let reported = self.symbols.diagnostics.len();
+        let storage_name = self.symbols.resolve(name, span);
+        if self.symbols.diagnostics.len() > reported {
+            return; // undeclared
+        }
+        let Some(&size) = self.arrays.get(&storage_name) else {
+            self.error(format!("'{name}' is not an array"), span);
+            return;
+        };
+
+        if let AstNode::Constant { value, span } = index {
+            if !value
+                .parse::<i128>()
+                .is_ok_and(|value| (0..size as i128).contains(&value))
+            {
+                self.error(
+                    format!("Index {value} is out of bounds for array '{name}' of size {size}"),
+                    *span,
+                );
+            }
+        }
+    }
+
+    fn visit_block(&mut self, node: &AstNode) {
+        self.symbols.enter_block();
+        self.visit(node);
+        self.symbols.exit_block();
+    }
+
+    fn error(&mut self, message: impl Into<String>, span: Span) {
+        self.symbols
+            .diagnostics
+            .push(Diagnostic::error(message).with_span(span));
+    }
+}
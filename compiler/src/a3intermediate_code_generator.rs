@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+
 use crate::a2parser::AstNode;
+use crate::diagnostics::{Diagnostic, Span};
 
 #[derive(Debug, Clone)]
 pub enum TACInstruction {
@@ -12,6 +15,26 @@ pub enum TACInstruction {
         operator: String,
         right: String,
     },
+    UnaryOperation {
+        result: String,
+        operator: String,
+        operand: String,
+    },
+    ArrayDeclaration {
+        name: String,
+        size: usize,
+    },
+    // An index that is a constant is kept as a number, so the element can be found at compile time
+    ArrayLoad {
+        result: String,
+        array: String,
+        index: String,
+    },
+    ArrayStore {
+        array: String,
+        index: String,
+        value: String,
+    },
     Label {
         label: String,
     },
@@ -29,12 +52,19 @@ pub enum TACInstruction {
     Return {
         value: String,
     },
+    Print {
+        value: String,
+    },
     FunctionCall {
         name: String,
         args: Vec<String>,
     },
     Function {
         name: String,
+        params: Vec<String>,
+    },
+    EndFunction {
+        name: String,
     },
 }
 
@@ -52,6 +82,30 @@ impl TACInstruction {
             TACInstruction::Assignment { var_name, value } => {
                 format!("{var_name} = {value}")
             }
+            TACInstruction::UnaryOperation {
+                result,
+                operator,
+                operand,
+            } => {
+                format!("{result} = {operator}{operand}")
+            }
+            TACInstruction::ArrayDeclaration { name, size } => {
+                format!("array {name}[{size}]")
+            }
+            TACInstruction::ArrayLoad {
+                result,
+                array,
+                index,
+            } => {
+                format!("{result} = {array}[{index}]")
+            }
+            TACInstruction::ArrayStore {
+                array,
+                index,
+                value,
+            } => {
+                format!("{array}[{index}] = {value}")
+            }
             TACInstruction::Label { label } => {
                 format!("{label}:")
             }
@@ -67,8 +121,14 @@ impl TACInstruction {
             TACInstruction::Return { value } => {
                 format!("return {value}")
             }
-            TACInstruction::Function { name } => {
-                format!("{name}:")
+            TACInstruction::Print { value } => {
+                format!("print {value}")
+            }
+            TACInstruction::Function { name, params } => {
+                format!("{name}({}):", params.join(", "))
+            }
+            TACInstruction::EndFunction { name } => {
+                format!("end {name}")
             }
             TACInstruction::FunctionCall { name, args } => {
                 format!("call {name} {}", args.join(", "))
@@ -77,53 +137,149 @@ impl TACInstruction {
     }
 }
 
-pub fn tac_generator(ast: &AstNode) -> Vec<TACInstruction> {
+// Generate TAC for the whole program, variables get the storage names the semantic analysis gave them.
+// names maps the start of every variable name in the source code to its storage name.
+pub fn tac_generator(
+    ast: &AstNode,
+    names: &HashMap<usize, String>,
+) -> Result<Vec<TACInstruction>, Diagnostic> {
     let mut instructions = Vec::new();
     let mut temp_counter = 1;
 
-    generate_tac(ast, &mut instructions, &mut temp_counter);
+    generate_tac(ast, &mut instructions, &mut temp_counter, names)?;
 
-    instructions
+    Ok(instructions)
 }
 
 fn generate_tac(
     node: &AstNode,
     instructions: &mut Vec<TACInstruction>,
     temp_counter: &mut u32,
-) -> String {
+    names: &HashMap<usize, String>,
+) -> Result<String, Diagnostic> {
     match node {
         AstNode::Body(nodes) => {
             for node in nodes {
-                generate_tac(node, instructions, temp_counter);
+                generate_tac(node, instructions, temp_counter, names)?;
             }
-            return "".to_string();
+            return Ok("".to_string());
         }
-        AstNode::Declaration { var_name, value } => {
-            let right_tac = generate_tac(&*value, instructions, temp_counter);
+        AstNode::Declaration {
+            var_name,
+            value,
+            span,
+        } => {
+            // The new variable is only visible after its value is evaluated, so `let a = a + 1;` reads the old a
+            let right_tac = generate_tac(&*value, instructions, temp_counter, names)?;
+            let var_name = storage_name(names, var_name, *span)?;
 
             instructions.push(TACInstruction::Assignment {
                 var_name: var_name.clone(),
                 value: right_tac,
             });
 
-            return var_name.to_string();
+            return Ok(var_name);
+        }
+        AstNode::ArrayDeclaration {
+            var_name,
+            value,
+            size,
+            span,
+        } => {
+            let value = generate_tac(value, instructions, temp_counter, names)?;
+            let name = storage_name(names, var_name, *span)?;
+
+            instructions.push(TACInstruction::ArrayDeclaration {
+                name: name.clone(),
+                size: *size,
+            });
+            for index in 0..*size {
+                instructions.push(TACInstruction::ArrayStore {
+                    array: name.clone(),
+                    index: index.to_string(),
+                    value: value.clone(),
+                });
+            }
+
+            Ok(name)
+        }
+        AstNode::Index { name, index, span } => {
+            let index = generate_index(index, instructions, temp_counter, names)?;
+            let array = storage_name(names, name, *span)?;
+            let result = format!("t{}", *temp_counter);
+            *temp_counter += 1;
+            instructions.push(TACInstruction::ArrayLoad {
+                result: result.clone(),
+                array,
+                index,
+            });
+            Ok(result)
         }
-        AstNode::Assignment { var_name, value } => {
-            let right_tac = generate_tac(&*value, instructions, temp_counter);
+        AstNode::IndexAssignment {
+            var_name,
+            index,
+            value,
+            span,
+        } => {
+            let index = generate_index(index, instructions, temp_counter, names)?;
+            let value = generate_tac(value, instructions, temp_counter, names)?;
+            let array = storage_name(names, var_name, *span)?;
+            instructions.push(TACInstruction::ArrayStore {
+                array: array.clone(),
+                index,
+                value,
+            });
+            Ok(array)
+        }
+        AstNode::Assignment {
+            var_name,
+            value,
+            span,
+        } => {
+            let right_tac = generate_tac(&*value, instructions, temp_counter, names)?;
+            let var_name = storage_name(names, var_name, *span)?;
 
             instructions.push(TACInstruction::Assignment {
                 var_name: var_name.clone(),
                 value: right_tac,
             });
-            return var_name.to_string();
+            return Ok(var_name);
+        }
+        // Logical operators produce 1 or 0, the right side is only evaluated when needed
+        AstNode::BinaryOperation { operator, .. } | AstNode::UnaryOperation { operator, .. }
+            if is_logical(operator) =>
+        {
+            let false_label = format!("L{}", *temp_counter);
+            *temp_counter += 1;
+            let end_label = format!("L{}", *temp_counter);
+            *temp_counter += 1;
+            let result = format!("t{}", *temp_counter);
+            *temp_counter += 1;
+
+            generate_jump(node, &false_label, false, instructions, temp_counter, names)?;
+            instructions.push(TACInstruction::Assignment {
+                var_name: result.clone(),
+                value: "1".to_string(),
+            });
+            instructions.push(TACInstruction::Goto {
+                label: end_label.clone(),
+            });
+            instructions.push(TACInstruction::Label { label: false_label });
+            instructions.push(TACInstruction::Assignment {
+                var_name: result.clone(),
+                value: "0".to_string(),
+            });
+            instructions.push(TACInstruction::Label { label: end_label });
+
+            Ok(result)
         }
         AstNode::BinaryOperation {
             left,
             right,
             operator,
         } => {
-            let left_tac = generate_tac(&*left, instructions, temp_counter);
-            let right_tac = generate_tac(&*right, instructions, temp_counter);
+            let left_tac = generate_tac(&*left, instructions, temp_counter, names)?;
+            let right_tac = generate_tac(&*right, instructions, temp_counter, names)?;
             let result = format!("t{}", *temp_counter);
             *temp_counter += 1;
             instructions.push(TACInstruction::BinaryOperation {
@@ -132,24 +288,42 @@ fn generate_tac(
                 operator: operator.clone(),
                 right: right_tac,
             });
-            return result;
+            return Ok(result);
         }
-        AstNode::Constant { value } => {
+        AstNode::UnaryOperation {
+            operator, operand, ..
+        } => {
+            let operand_tac = generate_tac(operand, instructions, temp_counter, names)?;
+            let result = format!("t{}", *temp_counter);
+            *temp_counter += 1;
+            instructions.push(TACInstruction::UnaryOperation {
+                result: result.clone(),
+                operator: operator.clone(),
+                operand: operand_tac,
+            });
+            Ok(result)
+        }
+        AstNode::Constant { value, .. } => {
             let result = format!("t{}", *temp_counter);
             *temp_counter += 1;
             instructions.push(TACInstruction::Assignment {
                 var_name: result.clone(),
                 value: value.clone(),
             });
-            return result;
+            return Ok(result);
         }
-        AstNode::Variable { name } => {
-            return name.clone();
+        AstNode::Variable { name, span } => {
+            return storage_name(names, name, *span);
         }
-        AstNode::Return { value } => {
-            let value = generate_tac(&*value, instructions, temp_counter);
+        AstNode::Return { value, .. } => {
+            let value = generate_tac(&*value, instructions, temp_counter, names)?;
             instructions.push(TACInstruction::Return { value });
-            return "".to_string();
+            return Ok("".to_string());
+        }
+        AstNode::Print { value } => {
+            let value = generate_tac(&*value, instructions, temp_counter, names)?;
+            instructions.push(TACInstruction::Print { value });
+            Ok("".to_string())
         }
         AstNode::While { condition, body } => {
             let start_label = format!("L{}", *temp_counter);
@@ -161,14 +335,16 @@ fn generate_tac(
                 label: start_label.clone(),
             });
 
-            let condition_tac = generate_tac(&*condition, instructions, temp_counter);
-
-            instructions.push(TACInstruction::IfNotGoto {
-                condition: condition_tac,
-                label: end_label.clone(),
-            });
+            generate_jump(
+                condition,
+                &end_label,
+                false,
+                instructions,
+                temp_counter,
+                names,
+            )?;
 
-            generate_tac(&*body, instructions, temp_counter);
+            generate_tac(&*body, instructions, temp_counter, names)?;
 
             instructions.push(TACInstruction::Goto {
                 label: start_label.clone(),
@@ -178,25 +354,18 @@ fn generate_tac(
                 label: end_label.clone(),
             });
 
-            return "".to_string();
+            return Ok("".to_string());
         }
-        AstNode::FunctionCall { name, args } => {
+        AstNode::FunctionCall { name, args, .. } => {
             let mut arg_tac = Vec::new();
             let node = &**args;
 
             if let AstNode::CallArguments(arg_nodes) = node {
                 for arg in arg_nodes {
-                    arg_tac.push(generate_tac(arg, instructions, temp_counter));
+                    arg_tac.push(generate_tac(arg, instructions, temp_counter, names)?);
                 }
             } else {
-                panic!("Expected AstNode::Arguments");
-            }
-
-            for (i, arg) in arg_tac.iter().enumerate() {
-                instructions.push(TACInstruction::Assignment {
-                    var_name: format!("arg{}", i),
-                    value: arg.clone(),
-                });
+                return Err(Diagnostic::error("Expected AstNode::Arguments"));
             }
 
             instructions.push(TACInstruction::FunctionCall {
@@ -204,59 +373,71 @@ fn generate_tac(
                 name: name.clone(),
             });
 
-            return "ret".to_string();
+            // Copy the return value, so it isn't overwritten by the next call
+            let result = format!("t{}", *temp_counter);
+            *temp_counter += 1;
+            instructions.push(TACInstruction::Assignment {
+                var_name: result.clone(),
+                value: "ret".to_string(),
+            });
+
+            return Ok(result);
         }
-        AstNode::Function { name, args, body } => {
-            let mut arg_tac = Vec::new();
+        AstNode::Function {
+            name, args, body, ..
+        } => {
+            let mut params = Vec::new();
             let node = &**args;
 
             if let AstNode::Arguments(arg_nodes) = node {
                 for arg in arg_nodes {
-                    arg_tac.push(generate_tac(arg, instructions, temp_counter));
+                    if let AstNode::Variable { name, span } = arg {
+                        params.push(storage_name(names, name, *span)?);
+                    }
                 }
             } else {
-                panic!("Expected AstNode::Arguments");
+                return Err(Diagnostic::error("Expected AstNode::Arguments"));
             }
 
-            instructions.push(TACInstruction::Function { name: name.clone() });
+            // Skip over the function body when the code around it is executed
+            let skip_label = format!("L{}", *temp_counter);
+            *temp_counter += 1;
+            instructions.push(TACInstruction::Goto {
+                label: skip_label.clone(),
+            });
 
-            for (i, arg) in arg_tac.iter().enumerate() {
-                instructions.push(TACInstruction::Assignment {
-                    var_name: format!("arg{}", i),
-                    value: arg.clone(),
-                });
-            }
+            instructions.push(TACInstruction::Function {
+                name: name.clone(),
+                params,
+            });
+
+            generate_tac(&*body, instructions, temp_counter, names)?;
 
-            generate_tac(&*body, instructions, temp_counter);
+            instructions.push(TACInstruction::EndFunction { name: name.clone() });
+            instructions.push(TACInstruction::Label { label: skip_label });
 
-            return "".to_string();
+            return Ok("".to_string());
         }
         AstNode::Conditional {
             condition,
             then_branch,
             else_branch,
         } => {
-            let condition_tac = generate_tac(&*condition, instructions, temp_counter);
-
-            let true_label = format!("L{}", *temp_counter);
-            *temp_counter += 1;
             let false_label = format!("L{}", *temp_counter);
             *temp_counter += 1;
             let end_label = format!("L{}", *temp_counter);
             *temp_counter += 1;
 
-            instructions.push(TACInstruction::IfGoto {
-                condition: condition_tac,
-                label: true_label.clone(),
-            });
-            instructions.push(TACInstruction::Goto {
-                label: false_label.clone(),
-            });
+            generate_jump(
+                condition,
+                &false_label,
+                false,
+                instructions,
+                temp_counter,
+                names,
+            )?;
 
-            instructions.push(TACInstruction::Label {
-                label: true_label.clone(),
-            });
-            generate_tac(&*then_branch, instructions, temp_counter);
+            generate_tac(&*then_branch, instructions, temp_counter, names)?;
 
             if let Some(else_branch) = &else_branch {
                 instructions.push(TACInstruction::Goto {
@@ -267,7 +448,7 @@ fn generate_tac(
                     label: false_label.clone(),
                 });
 
-                generate_tac(&*else_branch, instructions, temp_counter);
+                generate_tac(&*else_branch, instructions, temp_counter, names)?;
 
                 instructions.push(TACInstruction::Label {
                     label: end_label.clone(),
@@ -278,14 +459,110 @@ fn generate_tac(
                 });
             }
 
-            return "".to_string();
+            return Ok("".to_string());
+        }
+        _ => Err(Diagnostic::error(format!("Unexpected node: {:?}", node))),
+    }
+}
+
+// Jump to label when the truth value of the condition equals jump_if, otherwise fall through.
+// && and || only evaluate their right side when the left side does not decide the result.
+fn generate_jump(
+    condition: &AstNode,
+    label: &str,
+    jump_if: bool,
+    instructions: &mut Vec<TACInstruction>,
+    temp_counter: &mut u32,
+    names: &HashMap<usize, String>,
+) -> Result<(), Diagnostic> {
+    match condition {
+        AstNode::UnaryOperation {
+            operator, operand, ..
+        } if operator == "!" => {
+            generate_jump(operand, label, !jump_if, instructions, temp_counter, names)
+        }
+        AstNode::BinaryOperation {
+            operator,
+            left,
+            right,
+        } if is_logical(operator) => {
+            // && jumps as soon as one side is false, || as soon as one side is true
+            let short_circuit = operator == "||";
+            if jump_if == short_circuit {
+                generate_jump(left, label, jump_if, instructions, temp_counter, names)?;
+                generate_jump(right, label, jump_if, instructions, temp_counter, names)
+            } else {
+                let skip_label = format!("L{}", *temp_counter);
+                *temp_counter += 1;
+                generate_jump(
+                    left,
+                    &skip_label,
+                    short_circuit,
+                    instructions,
+                    temp_counter,
+                    names,
+                )?;
+                generate_jump(right, label, jump_if, instructions, temp_counter, names)?;
+                instructions.push(TACInstruction::Label { label: skip_label });
+                Ok(())
+            }
         }
         _ => {
-            panic!("Unexpected node: {:?}", node);
+            let condition = generate_tac(condition, instructions, temp_counter, names)?;
+            let label = label.to_string();
+            if jump_if {
+                instructions.push(TACInstruction::IfGoto { condition, label });
+            } else {
+                instructions.push(TACInstruction::IfNotGoto { condition, label });
+            }
+            Ok(())
         }
     }
 }
 
+// Index of an array element, constants are used directly so the element is known at compile time
+fn generate_index(
+    index: &AstNode,
+    instructions: &mut Vec<TACInstruction>,
+    temp_counter: &mut u32,
+    names: &HashMap<usize, String>,
+) -> Result<String, Diagnostic> {
+    match index {
+        AstNode::Constant { value, .. } => Ok(value.clone()),
+        _ => generate_tac(index, instructions, temp_counter, names),
+    }
+}
+
+// Storage name the semantic analysis gave to the variable at span
+fn storage_name(
+    names: &HashMap<usize, String>,
+    name: &str,
+    span: Span,
+) -> Result<String, Diagnostic> {
+    names.get(&span.start).cloned().ok_or_else(|| {
+        Diagnostic::error(format!("Variable '{name}' was not resolved")).with_span(span)
+    })
+}
+
+fn is_logical(operator: &str) -> bool {
+    matches!(operator, "&&" | "||" | "!")
+}
+
+// Temporaries are named t1, t2, ..., variables from the code always contain "::"
+pub fn is_temporary(var: &str) -> bool {
+    var.len() > 1 && var.starts_with('t') && var[1..].chars().all(char::is_numeric)
+}
+
+// Parameters of main, they are the inputs of the program
+pub fn main_params(tac: &[TACInstruction]) -> Vec<String> {
+    tac.iter()
+        .find_map(|instruction| match instruction {
+            TACInstruction::Function { name, params } if name == "main" => Some(params.clone()),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
 pub fn tacvec_to_string(tac: &Vec<TACInstruction>) -> String {
     let mut result = String::new();
     for instruction in tac {
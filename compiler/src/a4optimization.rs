@@ -1,43 +1,34 @@
 use std::collections::HashMap;
 
+use crate::a3intermediate_code_generator::is_temporary;
 use crate::TACInstruction;
 
 // Optimize the TAC instructions
-pub fn optimize_tac(tac: Vec<TACInstruction>) -> Vec<TACInstruction> {
+pub fn optimize_tac(mut tac: Vec<TACInstruction>) -> Vec<TACInstruction> {
+    multiplication_to_shift(&mut tac);
+
     let mut optimized_tac = Vec::<TACInstruction>::new();
 
     let mut variables = HashMap::<String, String>::new();
 
     for instruction in &tac {
         if let TACInstruction::Assignment { var_name, value } = instruction {
-            if !is_temporary(var_name) && value.parse::<u8>().is_err() && value != "ret" {
+            if !is_temporary(var_name) && value.parse::<i128>().is_err() && value != "ret" {
                 variables.insert(value.to_string(), var_name.to_string());
             }
         }
     }
 
-    println!("Variables: {:?}", variables);
-
-    let mut in_function_args = false;
     for instruction in tac {
         match instruction {
             TACInstruction::Assignment {
                 ref var_name,
                 ref value,
             } => {
-                if !var_name.starts_with("arg") {
-                    in_function_args = false;
-                }
-                println!("variables: {:?}", variables);
-                println!("var_name: {}, value: {}", var_name, value);
                 if variables.get(value) == Some(var_name) {
-                    println!("Remove: {} = {}", var_name, value);
-                    if !var_name.starts_with("arg") || in_function_args || is_temporary(value) {
-                        continue;
-                    }
+                    continue;
                 } else if let Some(new_var_name) = variables.get(var_name) {
                     if is_temporary(var_name) {
-                        println!("Replace {} with {}", var_name, new_var_name);
                         optimized_tac.push(TACInstruction::Assignment {
                             var_name: new_var_name.clone(),
                             value: value.clone(),
@@ -46,13 +37,8 @@ pub fn optimize_tac(tac: Vec<TACInstruction>) -> Vec<TACInstruction> {
                     }
                 }
 
-                println!("Keep");
                 optimized_tac.push(instruction.clone());
             }
-            TACInstruction::Function { .. } => {
-                in_function_args = true;
-                optimized_tac.push(instruction);
-            }
             TACInstruction::BinaryOperation {
                 result,
                 left,
@@ -68,11 +54,47 @@ pub fn optimize_tac(tac: Vec<TACInstruction>) -> Vec<TACInstruction> {
                     operator,
                     right,
                 });
-
-                in_function_args = false;
+            }
+            TACInstruction::UnaryOperation {
+                result,
+                operator,
+                operand,
+            } => {
+                let operand = variables.get(&operand).cloned().unwrap_or(operand.clone());
+                let result = variables.get(&result).cloned().unwrap_or(result.clone());
+                optimized_tac.push(TACInstruction::UnaryOperation {
+                    result,
+                    operator,
+                    operand,
+                });
+            }
+            TACInstruction::ArrayLoad {
+                result,
+                array,
+                index,
+            } => {
+                let index = variables.get(&index).cloned().unwrap_or(index.clone());
+                let result = variables.get(&result).cloned().unwrap_or(result.clone());
+                optimized_tac.push(TACInstruction::ArrayLoad {
+                    result,
+                    array,
+                    index,
+                });
+            }
+            TACInstruction::ArrayStore {
+                array,
+                index,
+                value,
+            } => {
+                let index = variables.get(&index).cloned().unwrap_or(index.clone());
+                let value = variables.get(&value).cloned().unwrap_or(value.clone());
+                optimized_tac.push(TACInstruction::ArrayStore {
+                    array,
+                    index,
+                    value,
+                });
             }
             _ => {
-                in_function_args = false;
                 optimized_tac.push(instruction);
             }
         }
@@ -83,6 +105,85 @@ pub fn optimize_tac(tac: Vec<TACInstruction>) -> Vec<TACInstruction> {
     optimized_tac
 }
 
+// Replace multiplications by a constant power of two with a left shift, which is much cheaper on the tape.
+// The temporary holding the constant is changed into the shift amount, so it may only be assigned and used once.
+fn multiplication_to_shift(tac: &mut [TACInstruction]) {
+    let mut constants = HashMap::<String, (usize, u64)>::new(); // <temporary, (index of the assignment, value)>
+    let mut assignments = HashMap::<String, usize>::new();
+    let mut uses = HashMap::<String, usize>::new();
+
+    for (index, instruction) in tac.iter().enumerate() {
+        let used: Vec<&String> = match instruction {
+            TACInstruction::Assignment { var_name, value } => {
+                *assignments.entry(var_name.clone()).or_insert(0) += 1;
+                if let Ok(value) = value.parse::<u64>() {
+                    constants.insert(var_name.clone(), (index, value));
+                }
+                vec![value]
+            }
+            TACInstruction::BinaryOperation { left, right, .. } => vec![left, right],
+            TACInstruction::UnaryOperation { operand, .. } => vec![operand],
+            TACInstruction::ArrayLoad { index, .. } => vec![index],
+            TACInstruction::ArrayStore { index, value, .. } => vec![index, value],
+            TACInstruction::IfGoto { condition, .. }
+            | TACInstruction::IfNotGoto { condition, .. } => {
+                vec![condition]
+            }
+            TACInstruction::Return { value } | TACInstruction::Print { value } => vec![value],
+            TACInstruction::FunctionCall { args, .. } => args.iter().collect(),
+            _ => vec![],
+        };
+        for var in used {
+            *uses.entry(var.clone()).or_insert(0) += 1;
+        }
+    }
+
+    let shift_amount = |var: &String| {
+        let &(index, value) = constants.get(var)?;
+        let single = assignments.get(var) == Some(&1) && uses.get(var) == Some(&1);
+        (is_temporary(var) && single && value > 1 && value.is_power_of_two())
+            .then(|| (index, value.trailing_zeros()))
+    };
+
+    let mut changes = Vec::new();
+    for (index, instruction) in tac.iter().enumerate() {
+        if let TACInstruction::BinaryOperation {
+            left,
+            operator,
+            right,
+            ..
+        } = instruction
+        {
+            if operator != "*" {
+                continue;
+            }
+            if let Some((constant_index, amount)) = shift_amount(right) {
+                changes.push((index, left.clone(), right.clone(), constant_index, amount));
+            } else if let Some((constant_index, amount)) = shift_amount(left) {
+                changes.push((index, right.clone(), left.clone(), constant_index, amount));
+            }
+        }
+    }
+
+    for (index, value, constant, constant_index, amount) in changes {
+        if let TACInstruction::BinaryOperation {
+            left,
+            operator,
+            right,
+            ..
+        } = &mut tac[index]
+        {
+            *left = value;
+            *operator = "<<".to_string();
+            *right = constant.clone();
+        }
+        tac[constant_index] = TACInstruction::Assignment {
+            var_name: constant,
+            value: amount.to_string(),
+        };
+    }
+}
+
 // Remove gaps in the temporary variable numbering, and in label numbering
 fn reset_vars(tac: &mut Vec<TACInstruction>) {
     let mut var_counter = 1;
@@ -111,7 +212,7 @@ fn reset_vars(tac: &mut Vec<TACInstruction>) {
                 update_temp_var(&mut temp_var_map, &mut var_counter, var_name);
                 update_temp_var(&mut temp_var_map, &mut var_counter, value);
             }
-            TACInstruction::Return { value } => {
+            TACInstruction::Return { value } | TACInstruction::Print { value } => {
                 update_temp_var(&mut temp_var_map, &mut var_counter, value);
             }
             TACInstruction::FunctionCall { name, args } => {
@@ -120,7 +221,13 @@ fn reset_vars(tac: &mut Vec<TACInstruction>) {
                     update_temp_var(&mut temp_var_map, &mut var_counter, arg);
                 }
             }
-            TACInstruction::Function { name } => {
+            TACInstruction::Function { name, params } => {
+                update_function(&mut temp_function_map, &mut function_counter, name);
+                for param in params {
+                    update_temp_var(&mut temp_var_map, &mut var_counter, param);
+                }
+            }
+            TACInstruction::EndFunction { name } => {
                 update_function(&mut temp_function_map, &mut function_counter, name);
             }
             TACInstruction::BinaryOperation {
@@ -133,6 +240,35 @@ fn reset_vars(tac: &mut Vec<TACInstruction>) {
                 update_temp_var(&mut temp_var_map, &mut var_counter, left);
                 update_temp_var(&mut temp_var_map, &mut var_counter, right);
             }
+            TACInstruction::UnaryOperation {
+                result,
+                operator: _,
+                operand,
+            } => {
+                update_temp_var(&mut temp_var_map, &mut var_counter, result);
+                update_temp_var(&mut temp_var_map, &mut var_counter, operand);
+            }
+            TACInstruction::ArrayDeclaration { name, size: _ } => {
+                update_temp_var(&mut temp_var_map, &mut var_counter, name);
+            }
+            TACInstruction::ArrayLoad {
+                result,
+                array,
+                index,
+            } => {
+                update_temp_var(&mut temp_var_map, &mut var_counter, result);
+                update_temp_var(&mut temp_var_map, &mut var_counter, array);
+                update_temp_var(&mut temp_var_map, &mut var_counter, index);
+            }
+            TACInstruction::ArrayStore {
+                array,
+                index,
+                value,
+            } => {
+                update_temp_var(&mut temp_var_map, &mut var_counter, array);
+                update_temp_var(&mut temp_var_map, &mut var_counter, index);
+                update_temp_var(&mut temp_var_map, &mut var_counter, value);
+            }
         }
     }
 }
@@ -156,7 +292,7 @@ fn update_label(temp_map: &mut HashMap<String, String>, counter: &mut usize, lab
 }
 
 fn update_temp_var(temp_map: &mut HashMap<String, String>, counter: &mut usize, var: &mut String) {
-    if var.parse::<u8>().is_err() {
+    if var.parse::<i128>().is_err() {
         let entry = temp_map.entry(var.clone()).or_insert_with(|| {
             let new_name = format!("t{}", *counter);
             *counter += 1;
@@ -165,7 +301,3 @@ fn update_temp_var(temp_map: &mut HashMap<String, String>, counter: &mut usize,
         *var = entry.clone();
     }
 }
-
-fn is_temporary(var: &str) -> bool {
-    var.starts_with('t') && var[1..].chars().all(char::is_numeric)
-}
@@ -0,0 +1,180 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::a3intermediate_code_generator::{is_temporary, TACInstruction};
+
+#[derive(Debug)]
+pub struct Function {
+    params: Vec<String>,
+    start: usize, // index of the Function instruction
+    end: usize,   // index of the EndFunction instruction
+    calls: HashSet<String>,
+}
+
+// Information about every function in the TAC, used to decide what to save on the call stack
+#[derive(Debug)]
+pub struct Functions {
+    functions: HashMap<String, Function>,
+}
+
+impl Functions {
+    pub fn new(tac: &[TACInstruction]) -> Functions {
+        let mut functions = HashMap::new();
+        let mut current: Option<(String, Function)> = None;
+
+        for (index, instruction) in tac.iter().enumerate() {
+            match instruction {
+                TACInstruction::Function { name, params } => {
+                    let function = Function {
+                        params: params.clone(),
+                        start: index,
+                        end: index,
+                        calls: HashSet::new(),
+                    };
+                    current = Some((name.clone(), function));
+                }
+                TACInstruction::EndFunction { .. } => {
+                    if let Some((name, mut function)) = current.take() {
+                        function.end = index;
+                        functions.insert(name, function);
+                    }
+                }
+                TACInstruction::FunctionCall { name, .. } => {
+                    if let Some((_, function)) = current.as_mut() {
+                        function.calls.insert(name.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Functions { functions }
+    }
+
+    pub fn params(&self, name: &str) -> Option<&Vec<String>> {
+        self.functions.get(name).map(|function| &function.params)
+    }
+
+    // Check if calling function `from` can lead to a call of function `to`
+    pub fn reaches(&self, from: &str, to: &str) -> bool {
+        let mut visited = HashSet::new();
+        let mut todo = vec![from.to_string()];
+
+        while let Some(name) = todo.pop() {
+            if name == to {
+                return true;
+            }
+            if !visited.insert(name.clone()) {
+                continue;
+            }
+            if let Some(function) = self.functions.get(&name) {
+                todo.extend(function.calls.iter().cloned());
+            }
+        }
+
+        false
+    }
+
+    // Variables of `function` that can still be read after the call at `call_index` returns.
+    // Temporaries are saved when they are assigned anywhere before the call and used after it,
+    // this also covers the result of && and ||, which is assigned on both branches.
+    // Named variables are saved when they appear after the call, or anywhere in a loop around the call.
+    // Only the function's own variables are saved, globals keep the values the callee gave them.
+    pub fn saved_variables(
+        &self,
+        tac: &[TACInstruction],
+        function: &str,
+        call_index: usize,
+    ) -> Vec<String> {
+        let own_prefix = format!("{function}::");
+        let Some(function) = self.functions.get(function) else {
+            return Vec::new();
+        };
+
+        let labels: HashMap<&String, usize> = tac[function.start..call_index]
+            .iter()
+            .enumerate()
+            .filter_map(|(i, instruction)| match instruction {
+                TACInstruction::Label { label } => Some((label, function.start + i)),
+                _ => None,
+            })
+            .collect();
+
+        // Jumping back to a label before the call means the call is inside a loop
+        let mut start = call_index + 1;
+        for instruction in &tac[call_index + 1..=function.end] {
+            match instruction {
+                TACInstruction::Goto { label }
+                | TACInstruction::IfGoto { label, .. }
+                | TACInstruction::IfNotGoto { label, .. } => {
+                    if let Some(&label_index) = labels.get(label) {
+                        start = start.min(label_index);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let defined_before: HashSet<&String> = tac[function.start..call_index]
+            .iter()
+            .flat_map(|instruction| definitions_and_uses(instruction).0)
+            .collect();
+
+        let mut saved = Vec::new();
+        for (index, instruction) in tac.iter().enumerate().take(function.end + 1).skip(start) {
+            let (definitions, uses) = definitions_and_uses(instruction);
+            for var in definitions.into_iter().chain(uses.iter().copied()) {
+                let needed = if is_temporary(var) {
+                    index > call_index && uses.contains(&var) && defined_before.contains(var)
+                } else {
+                    var.starts_with(&own_prefix)
+                };
+                if needed && !saved.contains(var) {
+                    saved.push(var.clone());
+                }
+            }
+        }
+
+        saved
+    }
+}
+
+// Variables written and read by an instruction, constants and the return value are left out
+fn definitions_and_uses(instruction: &TACInstruction) -> (Vec<&String>, Vec<&String>) {
+    let (definitions, uses) = match instruction {
+        TACInstruction::Assignment { var_name, value } => (vec![var_name], vec![value]),
+        TACInstruction::BinaryOperation {
+            result,
+            left,
+            right,
+            ..
+        } => (vec![result], vec![left, right]),
+        TACInstruction::UnaryOperation {
+            result, operand, ..
+        } => (vec![result], vec![operand]),
+        TACInstruction::ArrayDeclaration { name, .. } => (vec![name], vec![]),
+        TACInstruction::ArrayLoad {
+            result,
+            array,
+            index,
+        } => (vec![result], vec![array, index]),
+        // Storing one element keeps the other elements, so the array is also read
+        TACInstruction::ArrayStore {
+            array,
+            index,
+            value,
+        } => (vec![array], vec![array, index, value]),
+        TACInstruction::IfGoto { condition, .. } | TACInstruction::IfNotGoto { condition, .. } => {
+            (vec![], vec![condition])
+        }
+        TACInstruction::Return { value } | TACInstruction::Print { value } => (vec![], vec![value]),
+        TACInstruction::FunctionCall { args, .. } => (vec![], args.iter().collect()),
+        TACInstruction::Function { params, .. } => (params.iter().collect(), vec![]),
+        _ => (vec![], vec![]),
+    };
+
+    let is_variable = |var: &&String| var.as_str() != "ret" && var.parse::<i128>().is_err();
+    (
+        definitions.into_iter().filter(is_variable).collect(),
+        uses.into_iter().filter(is_variable).collect(),
+    )
+}
@@ -1,4 +1,5 @@
 pub use super::*;
+use crate::diagnostics::Diagnostic;
 
 pub fn assemblyvec_to_string(assembly: Vec<AssemblyInstruction>) -> String {
     let mut result = String::new();
@@ -10,20 +11,29 @@ pub fn assemblyvec_to_string(assembly: Vec<AssemblyInstruction>) -> String {
     result
 }
 
-pub fn operator_char_to_string(op: &str) -> String {
-    match op {
-        "+" => "ADD".to_string(),
-        "-" => "SUB".to_string(),
-        "*" => "MUL".to_string(),
-        "/" => "DIV".to_string(),
-        "=" => "MOV".to_string(),
-        "==" => "CMP".to_string(),
-        ">" => "GT".to_string(),
-        ">=" => "GE".to_string(),
-        "<" => "LT".to_string(),
-        "<=" => "LE".to_string(),
-        _ => panic!("Unsupported operator"),
-    }
+pub fn operator_char_to_string(op: &str) -> Result<String, Diagnostic> {
+    let operation = match op {
+        "+" => "ADD",
+        "-" => "SUB",
+        "*" => "MUL",
+        "/" => "DIV",
+        "%" => "MOD",
+        "&" => "AND",
+        "|" => "OR",
+        "^" => "XOR",
+        "~" => "INVERT",
+        "<<" => "SHL",
+        ">>" => "SHR",
+        "=" => "MOV",
+        "==" => "CMP",
+        "!=" => "NE",
+        ">" => "GT",
+        ">=" => "GE",
+        "<" => "LT",
+        "<=" => "LE",
+        _ => return Err(Diagnostic::error(format!("Unsupported operator '{op}'"))),
+    };
+    Ok(operation.to_string())
 }
 
 pub fn tvar_to_svar(tvar: &mut String) {
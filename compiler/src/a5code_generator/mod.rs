@@ -1,16 +1,22 @@
+mod functions;
 pub mod helpers;
 mod variables;
 
 use std::collections::HashMap;
 
-use self::{helpers::helpers::*, variables::Variables};
+pub use self::variables::StorageLocation;
+use self::{functions::Functions, helpers::helpers::*, variables::Variables};
 use crate::a3intermediate_code_generator::TACInstruction;
+use crate::diagnostics::Diagnostic;
+use crate::options::CompileOptions;
 
+// The variants are named after the instructions in the assembly listing
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum AssemblyInstruction {
     SET {
         destination: String,
-        value: u8,
+        value: u64,
     },
     LOAD {
         destination: String,
@@ -24,6 +30,12 @@ pub enum AssemblyInstruction {
         destination: String,
         source: String,
     }, // Storage to storage
+    LOADI {
+        source: String,
+    }, // Element B of the array in storage to A
+    STOREI {
+        destination: String,
+    }, // A to element B of the array in storage
     JMP {
         label: String,
     },
@@ -40,13 +52,31 @@ pub enum AssemblyInstruction {
     SUB,
     SUBSAFE,
     MUL,
+    DIV,
+    MOD,
+    AND,
+    OR,
+    XOR,
+    INVERT,
+    NEG,
+    SHL,
+    SHR,
+    SAR,
+    FLIPSIGN,
     NOT,
     ISZERO,
+    PRINT, // A to the end of the output
     ENDFN {
         name: String,
         address: String,
-        total: u8,
+        total: u32,
     },
+    PUSH {
+        source: String,
+    }, // Storage to top of the call stack
+    POP {
+        destination: String,
+    }, // Top of the call stack to storage
 }
 
 impl AssemblyInstruction {
@@ -69,6 +99,8 @@ impl AssemblyInstruction {
             } => {
                 format!("MOVE {source} {destination}")
             }
+            AssemblyInstruction::LOADI { source } => format!("LOADI {source}[B] A"),
+            AssemblyInstruction::STOREI { destination } => format!("STOREI {destination}[B] A"),
             AssemblyInstruction::JMP { label } => format!("JMP {}", label),
             AssemblyInstruction::JNZ { label } => format!("JNZ {}", label),
             AssemblyInstruction::LABEL { label } => format!("{}:", label),
@@ -78,30 +110,83 @@ impl AssemblyInstruction {
             AssemblyInstruction::SUBSAFE => "SUB_SAFE".to_string(),
             AssemblyInstruction::NOT => "NOT".to_string(),
             AssemblyInstruction::MUL => "MUL".to_string(),
+            AssemblyInstruction::DIV => "DIV".to_string(),
+            AssemblyInstruction::MOD => "MOD".to_string(),
+            AssemblyInstruction::AND => "AND".to_string(),
+            AssemblyInstruction::OR => "OR".to_string(),
+            AssemblyInstruction::XOR => "XOR".to_string(),
+            AssemblyInstruction::INVERT => "INVERT".to_string(),
+            AssemblyInstruction::NEG => "NEG".to_string(),
+            AssemblyInstruction::SHL => "SHL".to_string(),
+            AssemblyInstruction::SHR => "SHR".to_string(),
+            AssemblyInstruction::SAR => "SAR".to_string(),
+            AssemblyInstruction::FLIPSIGN => "FLIP_SIGN".to_string(),
             AssemblyInstruction::ISZERO => "ISZERO".to_string(),
+            AssemblyInstruction::PRINT => "PRINT".to_string(),
             AssemblyInstruction::ENDFN {
                 total,
                 name,
                 address,
             } => format!("ENDFN {total} {name} {address}"),
+            AssemblyInstruction::PUSH { source } => format!("PUSH {source}"),
+            AssemblyInstruction::POP { destination } => format!("POP {destination}"),
         }
     }
 }
 
-pub fn code_generator(tac: Vec<TACInstruction>) -> (Vec<AssemblyInstruction>, i32) {
+// LOAD left in A and right in B, run the instructions and STORE A in result
+fn binary_operation(
+    code: &mut Vec<AssemblyInstruction>,
+    left: &str,
+    right: &str,
+    instructions: impl IntoIterator<Item = AssemblyInstruction>,
+    result: &str,
+) {
+    code.push(AssemblyInstruction::LOAD {
+        destination: "A".to_string(),
+        source: left.to_string(),
+    });
+    code.push(AssemblyInstruction::LOAD {
+        destination: "B".to_string(),
+        source: right.to_string(),
+    });
+    code.extend(instructions);
+    code.push(AssemblyInstruction::STORE {
+        destination: result.to_string(),
+        source: "A".to_string(),
+    });
+}
+
+// Store (value XOR sign) - sign in result, the absolute value when sign is the sign of value spread over all bits
+fn absolute_value(code: &mut Vec<AssemblyInstruction>, value: &str, sign: &str, result: &str) {
+    binary_operation(code, value, sign, [AssemblyInstruction::XOR], result);
+    binary_operation(code, result, sign, [AssemblyInstruction::SUB], result);
+}
+
+pub fn code_generator(
+    tac: Vec<TACInstruction>,
+    options: &CompileOptions,
+) -> Result<(Vec<AssemblyInstruction>, i32, Vec<StorageLocation>), Diagnostic> {
     let mut variables = Variables::new();
     let mut code = Vec::new();
     let mut functions: HashMap<String, i32> = HashMap::new(); // <function_name, number_of_calls>
     let mut latest_func: String = "main".to_string();
-
-    for instruction in tac {
+    let function_table = Functions::new(&tac);
+    let arrays: HashMap<String, usize> = tac
+        .iter()
+        .filter_map(|instruction| match instruction {
+            TACInstruction::ArrayDeclaration { name, size } => Some((name.clone(), *size)),
+            _ => None,
+        })
+        .collect();
+
+    for (index, instruction) in tac.clone().into_iter().enumerate() {
         match instruction {
             TACInstruction::Assignment { var_name, value } => {
-                println!("var_name: {}, {}", var_name, value);
-                if value.parse::<u8>().is_ok() {
+                if let Ok(value) = value.parse::<i128>() {
                     code.push(AssemblyInstruction::SET {
                         destination: var_name.clone(),
-                        value: value.parse().unwrap(),
+                        value: options.encode(value),
                     });
                     variables.set(var_name.clone());
                 } else {
@@ -138,11 +223,29 @@ pub fn code_generator(tac: Vec<TACInstruction>) -> (Vec<AssemblyInstruction>, i3
             TACInstruction::Label { label } => {
                 code.push(AssemblyInstruction::LABEL { label });
             }
-            TACInstruction::Function { name } => {
+            TACInstruction::Function { name, params } => {
                 latest_func = name.clone();
-                functions.insert(name.clone(), 0);
+                functions.entry(name.clone()).or_insert(0);
+                for param in params {
+                    variables.set(param);
+                }
                 code.push(AssemblyInstruction::FN { name });
             }
+            TACInstruction::EndFunction { name } => {
+                // Functions without a return statement return whatever is in ret
+                if name == "main" {
+                    code.push(AssemblyInstruction::JMP {
+                        label: "L_end".to_string(),
+                    });
+                } else {
+                    code.push(AssemblyInstruction::ENDFN {
+                        name: name.clone(),
+                        address: format!("F_{name}"),
+                        total: 0,
+                    });
+                }
+                latest_func = "main".to_string();
+            }
             TACInstruction::Return { value } => {
                 let fn_name = latest_func.clone();
                 variables.set(value.clone());
@@ -152,6 +255,9 @@ pub fn code_generator(tac: Vec<TACInstruction>) -> (Vec<AssemblyInstruction>, i3
                         destination: "A".to_string(),
                         source: value.clone(),
                     });
+                    code.push(AssemblyInstruction::JMP {
+                        label: "L_end".to_string(),
+                    });
                 } else {
                     code.push(AssemblyInstruction::MOVE {
                         destination: "ret".to_string(),
@@ -164,12 +270,103 @@ pub fn code_generator(tac: Vec<TACInstruction>) -> (Vec<AssemblyInstruction>, i3
                     });
                 }
             }
-            TACInstruction::FunctionCall { name, args: _ } => {
+            TACInstruction::Print { value } => {
+                variables.set(value.clone());
+
+                code.push(AssemblyInstruction::LOAD {
+                    destination: "A".to_string(),
+                    source: value.clone(),
+                });
+                code.push(AssemblyInstruction::PRINT);
+            }
+            TACInstruction::FunctionCall { name, args } => {
+                let params = function_table.params(&name).ok_or_else(|| {
+                    Diagnostic::error(format!("Call to undefined function {name}"))
+                })?;
+                if params.len() != args.len() {
+                    return Err(Diagnostic::error(format!(
+                        "Function {name} expects {} arguments, but got {}",
+                        params.len(),
+                        args.len()
+                    )));
+                }
+
+                // If the called function can end up calling the current function again,
+                // the variables of the current function are saved on the call stack.
+                let mut saved = Vec::new();
+                if latest_func != "main" && function_table.reaches(&name, &latest_func) {
+                    saved = function_table.saved_variables(&tac, &latest_func, index);
+                    saved.push(format!("F_{latest_func}"));
+                }
+
+                for var in &saved {
+                    variables.set(var.clone());
+                }
+
+                // Every element of an array is saved
+                let saved: Vec<String> = saved
+                    .into_iter()
+                    .flat_map(|var| match arrays.get(&var) {
+                        Some(&size) => (0..size).map(|i| format!("{var}[{i}]")).collect(),
+                        None => vec![var],
+                    })
+                    .collect();
+                for var in &saved {
+                    code.push(AssemblyInstruction::PUSH {
+                        source: var.clone(),
+                    });
+                }
+
+                // Arguments that are parameters of the called function are copied first,
+                // so they are not overwritten before they are read.
+                let mut moves = Vec::new();
+                for (i, (arg, param)) in args.iter().zip(params).enumerate() {
+                    if arg != param && params.contains(arg) {
+                        let staging = format!("arg{i}");
+                        code.push(AssemblyInstruction::MOVE {
+                            destination: staging.clone(),
+                            source: arg.clone(),
+                        });
+                        variables.set(staging.clone());
+                        moves.push((staging, param));
+                    } else {
+                        moves.push((arg.clone(), param));
+                    }
+                }
+                for (arg, param) in moves {
+                    if &arg == param {
+                        continue;
+                    }
+                    variables.set(arg.clone());
+                    variables.set(param.clone());
+                    if let Ok(value) = arg.parse::<i128>() {
+                        code.push(AssemblyInstruction::SET {
+                            destination: param.clone(),
+                            value: options.encode(value),
+                        });
+                    } else {
+                        code.push(AssemblyInstruction::MOVE {
+                            destination: param.clone(),
+                            source: arg,
+                        });
+                    }
+                }
+
                 let entry = functions.entry(name.clone()).or_insert(0);
 
+                // The call site is numbered in F_<fn>, the number has to fit in a word
+                let max_value = options.encode(-1);
+                if *entry as u64 > max_value {
+                    return Err(Diagnostic::error(format!(
+                        "Function '{name}' has more than {} call sites, which do not fit in {} bits",
+                        max_value + 1,
+                        options.word_bits
+                    )));
+                }
+
                 code.push(AssemblyInstruction::SET {
                     destination: format!("F_{}", name.clone()),
-                    value: entry.clone() as u8,
+                    value: *entry as u64,
                 });
 
                 code.push(AssemblyInstruction::JMP {
@@ -181,15 +378,113 @@ pub fn code_generator(tac: Vec<TACInstruction>) -> (Vec<AssemblyInstruction>, i3
                 });
 
                 *entry += 1;
+
+                for var in saved.into_iter().rev() {
+                    code.push(AssemblyInstruction::POP { destination: var });
+                }
+            }
+
+            TACInstruction::ArrayDeclaration { name, size } => {
+                variables.set_array(name, size);
+            }
+            TACInstruction::ArrayLoad {
+                result,
+                array,
+                index,
+            } => {
+                variables.set(result.clone());
+                variables.set(array.clone());
+
+                // Elements at a constant index are copied like variables
+                if index.parse::<usize>().is_ok() {
+                    code.push(AssemblyInstruction::MOVE {
+                        destination: result,
+                        source: format!("{array}[{index}]"),
+                    });
+                } else {
+                    variables.set(index.clone());
+
+                    code.push(AssemblyInstruction::LOAD {
+                        destination: "B".to_string(),
+                        source: index,
+                    });
+
+                    code.push(AssemblyInstruction::LOADI { source: array });
+
+                    code.push(AssemblyInstruction::STORE {
+                        destination: result,
+                        source: "A".to_string(),
+                    });
+                }
+            }
+            TACInstruction::ArrayStore {
+                array,
+                index,
+                value,
+            } => {
+                variables.set(array.clone());
+                variables.set(value.clone());
+
+                if index.parse::<usize>().is_ok() {
+                    code.push(AssemblyInstruction::MOVE {
+                        destination: format!("{array}[{index}]"),
+                        source: value,
+                    });
+                } else {
+                    variables.set(index.clone());
+
+                    code.push(AssemblyInstruction::LOAD {
+                        destination: "A".to_string(),
+                        source: value,
+                    });
+
+                    code.push(AssemblyInstruction::LOAD {
+                        destination: "B".to_string(),
+                        source: index,
+                    });
+
+                    code.push(AssemblyInstruction::STOREI { destination: array });
+                }
             }
+            TACInstruction::UnaryOperation {
+                result,
+                operator,
+                operand,
+            } => {
+                // A unary "-" negates the operand instead of subtracting
+                let instruction = match operator.as_str() {
+                    "-" => AssemblyInstruction::NEG,
+                    "~" => AssemblyInstruction::INVERT,
+                    _ => {
+                        let operation = operator_char_to_string(&operator)?;
+                        return Err(Diagnostic::error(format!(
+                            "Operation {operation} is not supported"
+                        )));
+                    }
+                };
+
+                variables.set(result.clone());
+                variables.set(operand.clone());
+
+                code.push(AssemblyInstruction::LOAD {
+                    destination: "A".to_string(),
+                    source: operand.clone(),
+                });
 
+                code.push(instruction);
+
+                code.push(AssemblyInstruction::STORE {
+                    destination: result.clone(),
+                    source: "A".to_string(),
+                });
+            }
             TACInstruction::BinaryOperation {
                 result,
                 left,
                 operator,
                 right,
             } => {
-                let operation = operator_char_to_string(&operator);
+                let operation = operator_char_to_string(&operator)?;
 
                 variables.set(result.clone());
                 variables.set(left.clone());
@@ -262,6 +557,9 @@ pub fn code_generator(tac: Vec<TACInstruction>) -> (Vec<AssemblyInstruction>, i3
                             source: right.clone(),
                         });
 
+                        if options.signed {
+                            code.push(AssemblyInstruction::FLIPSIGN);
+                        }
                         code.push(AssemblyInstruction::SUBSAFE);
                         code.push(AssemblyInstruction::ISZERO);
                         code.push(AssemblyInstruction::NOT);
@@ -282,6 +580,9 @@ pub fn code_generator(tac: Vec<TACInstruction>) -> (Vec<AssemblyInstruction>, i3
                             source: left.clone(),
                         });
 
+                        if options.signed {
+                            code.push(AssemblyInstruction::FLIPSIGN);
+                        }
                         code.push(AssemblyInstruction::SUBSAFE);
                         code.push(AssemblyInstruction::ISZERO);
                         code.push(AssemblyInstruction::NOT);
@@ -291,6 +592,41 @@ pub fn code_generator(tac: Vec<TACInstruction>) -> (Vec<AssemblyInstruction>, i3
                             source: "A".to_string(),
                         });
                     }
+                    "NE" => {
+                        binary_operation(
+                            &mut code,
+                            &left,
+                            &right,
+                            [
+                                AssemblyInstruction::SUB,
+                                AssemblyInstruction::ISZERO,
+                                AssemblyInstruction::NOT,
+                            ],
+                            &result,
+                        );
+                    }
+                    "GE" => {
+                        let flip = options.signed.then_some(AssemblyInstruction::FLIPSIGN);
+                        binary_operation(
+                            &mut code,
+                            &right,
+                            &left,
+                            flip.into_iter()
+                                .chain([AssemblyInstruction::SUBSAFE, AssemblyInstruction::ISZERO]),
+                            &result,
+                        );
+                    }
+                    "LE" => {
+                        let flip = options.signed.then_some(AssemblyInstruction::FLIPSIGN);
+                        binary_operation(
+                            &mut code,
+                            &left,
+                            &right,
+                            flip.into_iter()
+                                .chain([AssemblyInstruction::SUBSAFE, AssemblyInstruction::ISZERO]),
+                            &result,
+                        );
+                    }
                     "MUL" => {
                         code.push(AssemblyInstruction::LOAD {
                             destination: "B".to_string(),
@@ -309,23 +645,139 @@ pub fn code_generator(tac: Vec<TACInstruction>) -> (Vec<AssemblyInstruction>, i3
                             source: "A".to_string(),
                         });
                     }
-                    _ => {}
+                    "AND" | "OR" | "XOR" | "SHL" | "SHR" => {
+                        code.push(AssemblyInstruction::LOAD {
+                            destination: "A".to_string(),
+                            source: left.clone(),
+                        });
+
+                        code.push(AssemblyInstruction::LOAD {
+                            destination: "B".to_string(),
+                            source: right.clone(),
+                        });
+
+                        code.push(match operation.as_str() {
+                            "AND" => AssemblyInstruction::AND,
+                            "OR" => AssemblyInstruction::OR,
+                            "XOR" => AssemblyInstruction::XOR,
+                            "SHL" => AssemblyInstruction::SHL,
+                            _ if options.signed => AssemblyInstruction::SAR,
+                            _ => AssemblyInstruction::SHR,
+                        });
+
+                        code.push(AssemblyInstruction::STORE {
+                            destination: result.clone(),
+                            source: "A".to_string(),
+                        });
+                    }
+                    "DIV" | "MOD" if options.signed => {
+                        // Divide the absolute values, |x| = (x XOR s) - s with s = x SAR (bits - 1),
+                        // then give the quotient the sign of left XOR right and the remainder the sign of left
+                        let shift = "#sign_shift".to_string();
+                        let left_sign = format!("{result}#left_sign");
+                        let right_sign = format!("{result}#right_sign");
+                        let left_abs = format!("{result}#left_abs");
+                        let right_abs = format!("{result}#right_abs");
+                        for name in [&shift, &left_sign, &right_sign, &left_abs, &right_abs] {
+                            variables.set(name.clone());
+                        }
+
+                        code.push(AssemblyInstruction::SET {
+                            destination: shift.clone(),
+                            value: options.word_bits as u64 - 1,
+                        });
+                        binary_operation(
+                            &mut code,
+                            &left,
+                            &shift,
+                            [AssemblyInstruction::SAR],
+                            &left_sign,
+                        );
+                        binary_operation(
+                            &mut code,
+                            &right,
+                            &shift,
+                            [AssemblyInstruction::SAR],
+                            &right_sign,
+                        );
+                        absolute_value(&mut code, &left, &left_sign, &left_abs);
+                        absolute_value(&mut code, &right, &right_sign, &right_abs);
+
+                        let sign = if operation == "DIV" {
+                            binary_operation(
+                                &mut code,
+                                &left_sign,
+                                &right_sign,
+                                [AssemblyInstruction::XOR],
+                                &right_sign,
+                            );
+                            binary_operation(
+                                &mut code,
+                                &left_abs,
+                                &right_abs,
+                                [AssemblyInstruction::DIV],
+                                &result,
+                            );
+                            right_sign
+                        } else {
+                            binary_operation(
+                                &mut code,
+                                &left_abs,
+                                &right_abs,
+                                [AssemblyInstruction::MOD],
+                                &result,
+                            );
+                            left_sign
+                        };
+                        // Negating with the sign is the same as taking the absolute value
+                        absolute_value(&mut code, &result, &sign, &result);
+                    }
+                    "DIV" | "MOD" => {
+                        code.push(AssemblyInstruction::LOAD {
+                            destination: "A".to_string(),
+                            source: left.clone(),
+                        });
+
+                        code.push(AssemblyInstruction::LOAD {
+                            destination: "B".to_string(),
+                            source: right.clone(),
+                        });
+
+                        if operation == "DIV" {
+                            code.push(AssemblyInstruction::DIV);
+                        } else {
+                            code.push(AssemblyInstruction::MOD);
+                        }
+
+                        code.push(AssemblyInstruction::STORE {
+                            destination: result.clone(),
+                            source: "A".to_string(),
+                        });
+                    }
+                    _ => {
+                        return Err(Diagnostic::error(format!(
+                            "Operation {operation} is not supported"
+                        )))
+                    }
                 }
             }
-            _ => panic!("Instruction {} not implemented", instruction.to_string()),
         }
     }
 
+    code.push(AssemblyInstruction::LABEL {
+        label: "L_end".to_string(),
+    });
+
     // add functions to variables
     for (name, frequency) in functions.clone() {
         variables.add(format!("F_{name}"), frequency as u32);
     }
 
-    // add ret variable, set frequency high so it will be at the start of the storage
-    variables.add("ret".to_string(), 1000);
-
-    println!("Functions: {:?}", functions.clone());
-    println!("Variables: {:?}", variables);
+    // ret and the parameters of main are at the start of the storage, so inputs can be written into the tape
+    variables.reserve("ret".to_string());
+    for param in function_table.params("main").into_iter().flatten() {
+        variables.reserve(param.clone());
+    }
 
     variables.calculate_addresses();
 
@@ -335,9 +787,11 @@ pub fn code_generator(tac: Vec<TACInstruction>) -> (Vec<AssemblyInstruction>, i3
             AssemblyInstruction::LOAD { destination, .. }
             | AssemblyInstruction::STORE { destination, .. }
             | AssemblyInstruction::SET { destination, .. }
-            | AssemblyInstruction::MOVE { destination, .. } => {
-                if let Some(var) = variables.get(destination) {
-                    *destination = var.get_address();
+            | AssemblyInstruction::MOVE { destination, .. }
+            | AssemblyInstruction::STOREI { destination }
+            | AssemblyInstruction::POP { destination } => {
+                if let Some(address) = variables.get_address(destination) {
+                    *destination = address;
                 }
             }
 
@@ -346,28 +800,30 @@ pub fn code_generator(tac: Vec<TACInstruction>) -> (Vec<AssemblyInstruction>, i3
         match instruction {
             AssemblyInstruction::LOAD { source, .. }
             | AssemblyInstruction::STORE { source, .. }
-            | AssemblyInstruction::MOVE { source, .. } => {
-                if let Some(var) = variables.get(source) {
-                    *source = var.get_address();
+            | AssemblyInstruction::MOVE { source, .. }
+            | AssemblyInstruction::LOADI { source }
+            | AssemblyInstruction::PUSH { source } => {
+                if let Some(address) = variables.get_address(source) {
+                    *source = address;
                 }
             }
             _ => {}
         }
-        match instruction {
-            AssemblyInstruction::ENDFN {
-                name,
-                address,
-                total,
-            } => {
-                if let Some(var) = variables.get(address) {
-                    *address = var.get_address();
-                }
-                let entry = functions.get(&name.clone()).unwrap();
-                *total = *entry as u8;
+        if let AssemblyInstruction::ENDFN {
+            name,
+            address,
+            total,
+        } = instruction
+        {
+            if let Some(var_address) = variables.get_address(address) {
+                *address = var_address;
             }
-            _ => {}
+            let entry = functions
+                .get(&name.clone())
+                .ok_or_else(|| Diagnostic::error(format!("Return from unknown function {name}")))?;
+            *total = *entry as u32;
         }
     }
 
-    (code, variables.count())
+    Ok((code, variables.count(), variables.locations()))
 }
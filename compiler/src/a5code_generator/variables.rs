@@ -1,26 +1,34 @@
+use std::cmp::Reverse;
 use std::collections::HashMap;
 
+use serde::Serialize;
+
+// Where a variable is stored, an array uses `size` cells starting at `address`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StorageLocation {
+    pub name: String,
+    pub address: usize,
+    pub size: usize,
+}
+
 #[derive(Debug)]
 pub struct Variable {
     frequency: u32,
     address: usize,
-}
-
-impl Variable {
-    pub fn get_address(&self) -> String {
-        format!("S{}", self.address)
-    }
+    size: usize, // number of storage cells, more than 1 for arrays
 }
 
 #[derive(Debug)]
 pub struct Variables {
     vars: HashMap<String, Variable>,
+    reserved: Vec<String>, // variables that get the first addresses, in this order
 }
 
 impl Variables {
     pub fn new() -> Variables {
         Variables {
             vars: HashMap::new(),
+            reserved: Vec::new(),
         }
     }
 
@@ -28,6 +36,7 @@ impl Variables {
         let var = Variable {
             frequency,
             address: self.vars.len(),
+            size: 1,
         };
         self.vars.insert(var_name, var);
     }
@@ -39,12 +48,38 @@ impl Variables {
             let var = Variable {
                 frequency: 1,
                 address: self.vars.len(),
+                size: 1,
             };
             self.vars.insert(var_name, var);
         }
     }
-    pub fn get(&self, var_name: &str) -> Option<&Variable> {
-        self.vars.get(var_name)
+
+    // Give a variable the next of the first addresses, no matter how often it is used
+    pub fn reserve(&mut self, var_name: String) {
+        if self.get_mut(&var_name).is_none() {
+            self.set(var_name.clone());
+        }
+        self.reserved.push(var_name);
+    }
+
+    // The elements of an array are stored in consecutive cells, element i is i cells after the first
+    pub fn set_array(&mut self, var_name: String, size: usize) {
+        self.set(var_name.clone());
+        if let Some(var) = self.get_mut(&var_name) {
+            var.size = size;
+        }
+    }
+
+    // Address of a variable, or of an array element written as "name[index]"
+    pub fn get_address(&self, var_name: &str) -> Option<String> {
+        if let Some(var) = self.vars.get(var_name) {
+            return Some(format!("S{}", var.address));
+        }
+
+        let (array, index) = var_name.strip_suffix(']')?.split_once('[')?;
+        let var = self.vars.get(array)?;
+        let index = index.parse::<usize>().ok()?;
+        Some(format!("S{}", var.address + index))
     }
     pub fn get_mut(&mut self, var_name: &str) -> Option<&mut Variable> {
         self.vars.get_mut(var_name)
@@ -52,14 +87,43 @@ impl Variables {
 
     // function to calculate addresses of all variables, give with most frequency, smallest address
     pub fn calculate_addresses(&mut self) {
-        let mut sorted_vars: Vec<&mut Variable> = self.vars.values_mut().collect();
-        sorted_vars.sort_by(|a, b| b.frequency.cmp(&a.frequency));
-        for (i, var) in sorted_vars.iter_mut().enumerate() {
-            var.address = i + 1;
+        let mut address = 1;
+        for var_name in &self.reserved {
+            let var = self.vars.get_mut(var_name).unwrap();
+            var.address = address;
+            address += var.size;
+        }
+
+        let mut sorted_vars: Vec<&mut Variable> = self
+            .vars
+            .iter_mut()
+            .filter(|(var_name, _)| !self.reserved.contains(var_name))
+            .map(|(_, var)| var)
+            .collect();
+        sorted_vars.sort_by_key(|var| Reverse(var.frequency));
+        for var in sorted_vars.iter_mut() {
+            var.address = address;
+            address += var.size;
         }
     }
 
+    // Where every variable is stored, sorted by address
+    pub fn locations(&self) -> Vec<StorageLocation> {
+        let mut locations: Vec<StorageLocation> = self
+            .vars
+            .iter()
+            .map(|(name, var)| StorageLocation {
+                name: name.clone(),
+                address: var.address,
+                size: var.size,
+            })
+            .collect();
+        locations.sort_by_key(|location| location.address);
+        locations
+    }
+
+    // Number of storage cells used by all variables
     pub fn count(&self) -> i32 {
-        self.vars.len() as i32
+        self.vars.values().map(|var| var.size as i32).sum()
     }
 }
@@ -29,8 +29,10 @@ ADD_DIGIT_ONE_1 (7,8) * L ADD_DIGIT_ONE_1
 ADD_DIGIT_ONE_1 0 8     L FIND_B_END
 ADD_DIGIT_ONE_1 1 7     L ADD_DIGIT_ONE_2
 
+# Carry the one to the left, a carry past the first bit is dropped
 ADD_DIGIT_ONE_2 0 1     R FIND_B_END
 ADD_DIGIT_ONE_2 1 0     L ADD_DIGIT_ONE_2
+ADD_DIGIT_ONE_2 2 *     R FIND_B_END
 
 # Return to the middle of the tape
 RESTORE 7 0             R RESTORE
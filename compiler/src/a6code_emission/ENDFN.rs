@@ -4,7 +4,7 @@ pub fn endfn_instructions(
     instruction_counter: &u32,
     name: String,
     address: u32,
-    total: u8,
+    total: u32,
 ) -> Vec<String> {
     let to_sn = go_to_storage(address, "SUB_0".to_string(), false);
 
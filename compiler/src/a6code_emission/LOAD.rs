@@ -79,10 +79,61 @@ TO_START Middle *                  S END"
     let mut string = part1 + &part2 + &part3 + &part4 + &part5 + &part6 + &part7;
 
     // replace all "[A/B/C]" with A,B or C depending on the working space
-    string = string.replace("[A/B/C]", &format!("{}", working_space));
+    string = string.replace("[A/B/C]", &working_space.to_string());
 
     // replace all "S[A]" with "S[address]"
     string = string.replace("S[a]", &format!("S{}", address));
 
     format_instructions(string, *instruction_counter)
 }
+
+// Load the element of the array at address into A, with the index in B
+pub fn loadi_instructions(instruction_counter: &u32, address: u32) -> Vec<String> {
+    let part1 = format!(
+        "
+# Start of loading, set all values of A to H0
+START Middle *          R TO_A_END_R
+TO_A_END_R (0,1,StartA) *   R TO_A_END_R
+TO_A_END_R ABsep *          L SET_TO_7
+SET_TO_7 0 H0               L SET_TO_7
+SET_TO_7 1 H0               L SET_TO_7
+SET_TO_7 StartA *           L TO_MIDDLE_L
+TO_MIDDLE_L Middle *        L TO_{address}_S1
+"
+    );
+
+    let part2 = go_to_storage(address, "TO_INDEX".to_string(), true)
+        .trim()
+        .to_string()
+        + "\n";
+
+    let part3 = go_to_element("COPY_VALUE") + "\n";
+
+    let part4 = "
+# Copy the first non-H0/H1 value of the element
+COPY_VALUE 0 H0         R MOVE_ZERO
+COPY_VALUE 1 H1         R MOVE_ONE
+COPY_VALUE (H0,H1) *    L COPY_VALUE
+COPY_VALUE StSep *      R RESTORE_VALUE
+
+# Go back to the marked element for the next bit
+TO_MARK (0,1,H0,StartA,Middle,StSep) *  L TO_MARK
+TO_MARK StMark *                        L COPY_VALUE
+"
+    .to_string();
+
+    let part5 = move_bits("A", "R", "TO_MARK") + "\n";
+
+    let part6 = "
+# Restore the element, translate H0 to 0 and H1 to 1, and remove the mark
+RESTORE_VALUE H0 0              R RESTORE_VALUE
+RESTORE_VALUE H1 1              R RESTORE_VALUE
+RESTORE_VALUE StMark StSep      R TO_START
+# Go back to the start
+TO_START (0,1,StSep) *          R TO_START
+TO_START Middle *               S END";
+
+    let string = part1 + &part2 + &part3 + &part4 + &part5 + part6;
+
+    format_instructions(string, *instruction_counter)
+}
@@ -2,7 +2,6 @@ use super::prelude::*;
 
 pub fn move_instruction(instruction_counter: &u32, source: u32, destination: u32) -> Vec<String> {
     let dir;
-    println!("source: {}, destination: {}", source, destination);
     if source > destination {
         dir = "R";
     } else {
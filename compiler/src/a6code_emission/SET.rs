@@ -1,6 +1,6 @@
 use super::prelude::*;
 
-pub fn set_instructions(instruction_counter: &u32, address: u32, value: [bool; 8]) -> Vec<String> {
+pub fn set_instructions(instruction_counter: &u32, address: u32, value: &[bool]) -> Vec<String> {
     let part1 = format!("\nSTART 5 5 L TO_{address}_S1\n");
 
     let part2 = go_to_storage(address, "RESET_S".to_string(), false)
@@ -16,11 +16,11 @@ RESET_S 6 * R SET_1D\n";
 
     let mut part4 = "".to_string();
 
-    for i in 0..8 {
+    for i in 0..value.len() {
         let state1 = format!("SET_{}D", i + 1);
         let mut state2 = format!("SET_{}D", i + 2);
 
-        if i == 7 {
+        if i == value.len() - 1 {
             state2 = "RETURN".to_string();
         }
         if value[i] {
@@ -37,7 +37,7 @@ RETURN (0,1,StSep) *    R RETURN
 RETURN Middle *         S END"
         .to_string();
 
-    let string = part1 + &part2 + &part3 + &part4 + &part5;
+    let string = part1 + &part2 + part3 + &part4 + &part5;
 
     format_instructions(string, *instruction_counter)
 }
@@ -12,6 +12,27 @@ pub fn store_instructions(
         .to_string()
         + "\n";
 
+    let string = part1 + &part2 + &store_value();
+    format_instructions(string, *instruction_counter)
+}
+
+// Store A in the element of the array at address, with the index in B
+pub fn storei_instructions(instruction_counter: &u32, address: u32) -> Vec<String> {
+    let part1 = format!("\nSTART 5 5 L TO_{address}_S1\n");
+
+    let part2 = go_to_storage(address, "TO_INDEX".to_string(), true)
+        .trim()
+        .to_string()
+        + "\n";
+
+    let part3 = go_to_element("SET_TO_7") + "\n";
+
+    let string = part1 + &part2 + &part3 + &store_value();
+    format_instructions(string, *instruction_counter)
+}
+
+// Copy A into the storage cell marked with StMark, starts on the last bit of the cell
+fn store_value() -> String {
     let part3 = "
 # Set all the values to H0 untill the start of S1
 SET_TO_7 0 H0                            L SET_TO_7
@@ -51,6 +72,5 @@ RETURN_TO_MIDDLE Middle Middle              S END
     .trim()
     .to_string();
 
-    let string = part1 + &part2 + &part3 + &part4 + &part5;
-    format_instructions(string, *instruction_counter)
+    part3 + &part4 + &part5
 }
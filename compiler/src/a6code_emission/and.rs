@@ -0,0 +1,5 @@
+use super::prelude::*;
+
+pub fn and_instructions(instruction_counter: &u32) -> Vec<String> {
+    format_instructions(bitwise_operation("AND", [0, 0, 0, 1]), *instruction_counter)
+}
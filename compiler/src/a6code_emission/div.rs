@@ -0,0 +1,175 @@
+use super::prelude::*;
+
+// Divide A by B, and store the quotient in A
+pub fn div_instructions(instruction_counter: &u32) -> Vec<String> {
+    let string = long_division()
+        + "
+# Restore the quotient in A
+FINISH ABsep *              L FINISH
+FINISH H0 0                 L FINISH
+FINISH H1 1                 L FINISH
+FINISH StartA *             L FINISH
+FINISH Middle *             S END";
+
+    format_instructions(string, *instruction_counter)
+}
+
+// Divide A by B, and store the remainder in A
+pub fn mod_instructions(instruction_counter: &u32) -> Vec<String> {
+    let string = long_division()
+        + "
+# Clear the quotient in A, every H0 is a free place for a bit of the remainder
+FINISH ABsep *              L FINISH
+FINISH (H0,H1) H0           L FINISH
+FINISH StartA *             R MOD_TO_C
+
+# Go to the end of C
+MOD_TO_C (0,1,H0,H1,ABsep,EndB) *   R MOD_TO_C
+MOD_TO_C EndC *                     L MOD_TAKE
+
+# Take the last bit of the remainder that has not been copied yet
+MOD_TAKE (H0,H1) *          L MOD_TAKE
+MOD_TAKE 0 H0               L MOD_PUT_0
+MOD_TAKE 1 H1               L MOD_PUT_1
+MOD_TAKE EndB *             R MOD_RESTORE
+
+# Put the bit on the last free place in A
+MOD_PUT_0 (0,1,EndB,ABsep) *    L MOD_PUT_0
+MOD_PUT_0 H0 0                  R MOD_TO_C
+MOD_PUT_1 (0,1,EndB,ABsep) *    L MOD_PUT_1
+MOD_PUT_1 H0 1                  R MOD_TO_C
+
+# Restore the remainder in C
+MOD_RESTORE H0 0            R MOD_RESTORE
+MOD_RESTORE H1 1            R MOD_RESTORE
+MOD_RESTORE EndC *          L RETURN
+
+# Return to the middle of the tape
+RETURN (0,1,StartA,ABsep,EndB) *    L RETURN
+RETURN Middle *                     S END";
+
+    format_instructions(string, *instruction_counter)
+}
+
+// Binary long division of A by B, one bit of A at a time.
+// The remainder is kept in C, and the bits of A are replaced by the bits of the quotient (as H0 and H1).
+// The bit of A that is being divided is marked with H2.
+// Dividing by zero gives all ones as quotient and A as remainder.
+// Ends in the FINISH state on ABsep.
+fn long_division() -> String {
+    "
+# Start of the binary long division, clear C for the remainder
+START Middle *              R TO_C
+TO_C (0,1,StartA,ABsep) *   R TO_C
+TO_C EndB *                 R CLEAR_C
+CLEAR_C (0,1) 0             R CLEAR_C
+CLEAR_C EndC *              L TO_START
+TO_START (0,1,ABsep,EndB) *         L TO_START
+TO_START StartA *                   R NEXT_BIT
+
+# Take the next bit of A
+NEXT_BIT 0 H2               R CARRY_0
+NEXT_BIT 1 H2               R CARRY_1
+NEXT_BIT ABsep *            S FINISH
+
+# Go to the end of C
+CARRY_0 (0,1,ABsep,EndB) *  R CARRY_0
+CARRY_0 EndC *              L SHIFT_0
+CARRY_1 (0,1,ABsep,EndB) *  R CARRY_1
+CARRY_1 EndC *              L SHIFT_1
+
+# Shift C to the left, and put the bit of A at the end
+SHIFT_0 0 0                 L SHIFT_0
+SHIFT_0 1 0                 L SHIFT_1
+SHIFT_1 0 1                 L SHIFT_0
+SHIFT_1 1 1                 L SHIFT_1
+
+# If a 1 was shifted out of C, the remainder is larger than B, otherwise compare C with B
+SHIFT_0 EndB *              R CMP_C
+SHIFT_1 EndB *              S SUB_TO_B
+
+## Compare C with B, from the first bit to the last
+
+# Take the first bit of C that has not been compared
+CMP_C (H0,H1) *             R CMP_C
+CMP_C 0 H0                  L CMP_B_0
+CMP_C 1 H1                  L CMP_B_1
+CMP_C EndC *                L RESTORE_GE
+
+# Go to the start of B
+CMP_B_0 (0,1,H0,H1,EndB) *  L CMP_B_0
+CMP_B_0 ABsep *             R CMP_B_0_1
+CMP_B_1 (0,1,H0,H1,EndB) *  L CMP_B_1
+CMP_B_1 ABsep *             R CMP_B_1_1
+
+# Compare with the first bit of B that has not been compared
+CMP_B_0_1 (H0,H1) *         R CMP_B_0_1
+CMP_B_0_1 0 H0              R CMP_NEXT
+CMP_B_0_1 1 H1              L RESTORE_LT
+CMP_B_1_1 (H0,H1) *         R CMP_B_1_1
+CMP_B_1_1 0 H0              L RESTORE_GE
+CMP_B_1_1 1 H1              R CMP_NEXT
+
+# Bits are equal, compare the next bit
+CMP_NEXT (0,1,H0,H1) *      R CMP_NEXT
+CMP_NEXT EndB *             R CMP_C
+
+# Remove the compare markers in B and C
+RESTORE_GE (0,1,H0,H1,EndB) *   L RESTORE_GE
+RESTORE_GE ABsep *              R CLEAN_GE
+CLEAN_GE H0 0                   R CLEAN_GE
+CLEAN_GE H1 1                   R CLEAN_GE
+CLEAN_GE (0,1,EndB) *           R CLEAN_GE
+CLEAN_GE EndC *                 L SUB_TO_B
+
+RESTORE_LT (0,1,H0,H1,EndB) *   L RESTORE_LT
+RESTORE_LT ABsep *              R CLEAN_LT
+CLEAN_LT H0 0                   R CLEAN_LT
+CLEAN_LT H1 1                   R CLEAN_LT
+CLEAN_LT (0,1,EndB) *           R CLEAN_LT
+CLEAN_LT EndC *                 L QUOTIENT_0
+
+## Subtract B from C, from the last bit to the first
+
+# Find the last bit of B that has not been subtracted
+SUB_TO_B (0,1) *            L SUB_TO_B
+SUB_TO_B EndB *             L SUB_B_DIGIT
+
+SUB_B_DIGIT (H0,H1) *       L SUB_B_DIGIT
+SUB_B_DIGIT 0 H0            R SUB_ZERO
+SUB_B_DIGIT 1 H1            R SUB_ONE
+SUB_B_DIGIT ABsep *         R CLEAN_SUB
+
+# Subtract a zero from the last bit of C that has not been subtracted
+SUB_ZERO (0,1,H0,H1,EndB) * R SUB_ZERO
+SUB_ZERO EndC *             L SUB_ZERO_1
+SUB_ZERO_1 (H0,H1) *        L SUB_ZERO_1
+SUB_ZERO_1 0 H0             L SUB_TO_B
+SUB_ZERO_1 1 H1             L SUB_TO_B
+
+# Subtract a one from the last bit of C that has not been subtracted
+SUB_ONE (0,1,H0,H1,EndB) *  R SUB_ONE
+SUB_ONE EndC *              L SUB_ONE_1
+SUB_ONE_1 (H0,H1) *         L SUB_ONE_1
+SUB_ONE_1 1 H0              L SUB_TO_B
+SUB_ONE_1 0 H1              L SUB_BORROW
+
+# Borrow a one from the bits to the left, a borrow past the first bit is dropped
+SUB_BORROW 0 1              L SUB_BORROW
+SUB_BORROW 1 0              L SUB_TO_B
+SUB_BORROW EndB *           S SUB_TO_B
+
+# Remove the subtract markers in B and C
+CLEAN_SUB H0 0              R CLEAN_SUB
+CLEAN_SUB H1 1              R CLEAN_SUB
+CLEAN_SUB (0,1,EndB) *      R CLEAN_SUB
+CLEAN_SUB EndC *            L QUOTIENT_1
+
+## Write the bit of the quotient in A, in place of the marked bit
+QUOTIENT_0 (0,1,ABsep,EndB) *   L QUOTIENT_0
+QUOTIENT_0 H2 H0                R NEXT_BIT
+QUOTIENT_1 (0,1,ABsep,EndB) *   L QUOTIENT_1
+QUOTIENT_1 H2 H1                R NEXT_BIT
+"
+    .to_string()
+}
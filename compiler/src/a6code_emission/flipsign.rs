@@ -0,0 +1,27 @@
+use super::prelude::*;
+
+// Flip the first bit of A and of B.
+// This turns two's complement numbers into unsigned numbers with the same order, so signed numbers
+// can be compared with the unsigned comparisons.
+pub fn flipsign_instructions(instruction_counter: &u32) -> Vec<String> {
+    let string = "
+# Start of the FLIP_SIGN program
+START Middle *          R FLIP_A
+
+# Flip the first bit of A
+FLIP_A StartA *         R FLIP_A_1
+FLIP_A_1 0 1            R TO_B
+FLIP_A_1 1 0            R TO_B
+
+# Flip the first bit of B
+TO_B (0,1) *            R TO_B
+TO_B ABsep *            R FLIP_B
+FLIP_B 0 1              L RETURN
+FLIP_B 1 0              L RETURN
+
+# Return to the middle of the tape
+RETURN (0,1,StartA,ABsep) * L RETURN
+RETURN Middle *             S END";
+
+    format_instructions(string.to_string(), *instruction_counter)
+}
@@ -50,7 +50,13 @@ pub fn move_bits(destination: &str, direction: &str, next_state: &str) -> String
             //             }
         }
 
-        string.push_str(&format!("\n{0} (Middle,StSep,H0,H1) * {1} {0}", state, dir));
+        // The marked cell of an array element is passed on the way to A, B or C
+        let passed = if destination == "StMark" {
+            "(Middle,StSep,H0,H1)"
+        } else {
+            "(Middle,StSep,StMark,H0,H1)"
+        };
+        string.push_str(&format!("\n{0} {1} * {2} {0}", state, passed, dir));
 
         string.push_str(&format!(
             "
@@ -80,8 +86,8 @@ pub fn go_to_storage(address: u32, next_state: String, mark: bool) -> String {
         ));
 
         for i in 2..=address {
-            let i_str = format!("S{}", i.to_string());
-            let next_i_str = format!("S{}", (i + 1).to_string());
+            let i_str = format!("S{i}");
+            let next_i_str = format!("S{}", i + 1);
 
             let state = format!("TO_{address}_{i_str}");
 
@@ -98,3 +104,171 @@ pub fn go_to_storage(address: u32, next_state: String, mark: bool) -> String {
 
     string
 }
+
+// Move the StMark of an array from its first element to the element with the index in B.
+// Starts on the first element after go_to_storage marked it, B is counted down to 0.
+// Ends in next_state on the last bit of the element.
+pub fn go_to_element(next_state: &str) -> String {
+    format!(
+        "
+# Go to B, the index of the element
+TO_INDEX (0,1,H0,StSep,StMark,Middle,StartA) *   R TO_INDEX
+TO_INDEX ABsep *                                R CHECK_INDEX
+
+# Stop when B is zero
+CHECK_INDEX 0 *             R CHECK_INDEX
+CHECK_INDEX 1 *             R DECREMENT_INDEX
+CHECK_INDEX EndB *          L TO_ELEMENT
+
+# Subtract one from B
+DECREMENT_INDEX (0,1) *     R DECREMENT_INDEX
+DECREMENT_INDEX EndB *      L DECREMENT_INDEX_1
+DECREMENT_INDEX_1 0 1       L DECREMENT_INDEX_1
+DECREMENT_INDEX_1 1 0       L NEXT_ELEMENT
+
+# Move the mark to the next element, one cell to the left
+NEXT_ELEMENT (0,1,H0,ABsep,StartA,Middle,StSep) *   L NEXT_ELEMENT
+NEXT_ELEMENT StMark StSep                           L NEXT_ELEMENT_1
+NEXT_ELEMENT_1 (0,1) *                              L NEXT_ELEMENT_1
+NEXT_ELEMENT_1 StSep StMark                         R TO_INDEX
+
+# Go to the marked element
+TO_ELEMENT (0,1,H0,ABsep,StartA,Middle,StSep) * L TO_ELEMENT
+TO_ELEMENT StMark *                             L {next_state}"
+    )
+}
+
+// Combine every bit of A with the bit at the same position in B, and store the result in A.
+// results gives the new bit of A for the bit pairs (A, B): (0, 0), (0, 1), (1, 0) and (1, 1).
+pub fn bitwise_operation(name: &str, results: [u8; 4]) -> String {
+    let [zero_zero, zero_one, one_zero, one_one] = results;
+
+    format!(
+        "
+# Start of the bitwise {name}
+START Middle *              R FIND_B_END
+
+# Find the end of B
+FIND_B_END (0,1,StartA,ABsep,H0,H1) *  R FIND_B_END
+FIND_B_END EndB *                       L TAKE_B
+
+# Take the last bit of B that has not been used
+TAKE_B (H0,H1) *            L TAKE_B
+TAKE_B 0 H0                 L TO_A_0
+TAKE_B 1 H1                 L TO_A_1
+TAKE_B ABsep *              L RESTORE_A
+
+# Go to the end of A
+TO_A_0 (0,1) *              L TO_A_0
+TO_A_0 ABsep *              L APPLY_0
+TO_A_1 (0,1) *              L TO_A_1
+TO_A_1 ABsep *              L APPLY_1
+
+# Combine with the last bit of A that has not been used
+APPLY_0 (H0,H1) *           L APPLY_0
+APPLY_0 0 H{zero_zero}      R FIND_B_END
+APPLY_0 1 H{one_zero}       R FIND_B_END
+APPLY_1 (H0,H1) *           L APPLY_1
+APPLY_1 0 H{zero_one}       R FIND_B_END
+APPLY_1 1 H{one_one}        R FIND_B_END
+
+# Restore the numbers in A and B
+RESTORE_A H0 0              L RESTORE_A
+RESTORE_A H1 1              L RESTORE_A
+RESTORE_A StartA *          R RESTORE_B
+RESTORE_B (0,1,ABsep) *     R RESTORE_B
+RESTORE_B H0 0              R RESTORE_B
+RESTORE_B H1 1              R RESTORE_B
+RESTORE_B EndB *            L RETURN
+
+# Return to the middle of the tape
+RETURN (0,1,StartA,ABsep) * L RETURN
+RETURN Middle *             S END"
+    )
+}
+
+// Shift A by the number in B, one bit at a time. B is counted down to 0.
+// direction is "L" to shift to the left and "R" to shift to the right, bits shifted out of A are dropped.
+// A 0 is shifted in, unless keep_sign is set for a right shift, then the first bit of A is shifted in.
+pub fn shift_operation(direction: &str, keep_sign: bool, word_bits: u32) -> String {
+    // A left shift walks from the last bit of A to the first, a right shift from the first to the last
+    let (to_a, shift_end) = if direction == "L" {
+        ("TO_A ABsep *  L SHIFT_0".to_string(), "StartA * R TO_B")
+    } else if keep_sign {
+        (
+            "TO_A ABsep *  L TO_A\nTO_A StartA * R SHIFT_SIGN\nSHIFT_SIGN 0 0 R SHIFT_0\nSHIFT_SIGN 1 1 R SHIFT_1"
+                .to_string(),
+            "ABsep * R CHECK_B",
+        )
+    } else {
+        (
+            "TO_A ABsep *  L TO_A\nTO_A StartA * R SHIFT_0".to_string(),
+            "ABsep * R CHECK_B",
+        )
+    };
+
+    // Shifting by word_bits already moves out every bit, so a larger B is set to word_bits.
+    // B is compared to word_bits from the first bit, CLAMP_i is reached when the first i bits are equal.
+    let word_bits = word_bits as usize;
+    let limit = format!("{word_bits:0word_bits$b}");
+    let mut clamp = String::new();
+    for (i, bit) in limit.chars().enumerate() {
+        let next = i + 1;
+        if bit == '0' {
+            clamp.push_str(&format!(
+                "\nCLAMP_{i} 0 *                R CLAMP_{next}\nCLAMP_{i} 1 0                R CLAMP_SET_{next}"
+            ));
+        } else {
+            clamp.push_str(&format!(
+                "\nCLAMP_{i} 0 *                L CLAMP_BACK\nCLAMP_{i} 1 *                R CLAMP_{next}"
+            ));
+        }
+        if i > 0 {
+            clamp.push_str(&format!(
+                "\nCLAMP_SET_{i} (0,1) {bit}       R CLAMP_SET_{next}"
+            ));
+        }
+    }
+
+    format!(
+        "
+# Start of the shift, go to the start of B
+START Middle *              R TO_B
+TO_B (0,1,StartA) *         R TO_B
+TO_B ABsep *                R CLAMP_0
+
+# Set B to {word_bits} when it is larger, B is larger at the first 1 where {limit} has a 0
+{clamp}
+CLAMP_{word_bits} EndB *          L CLAMP_BACK
+CLAMP_SET_{word_bits} EndB *      L CLAMP_BACK
+CLAMP_BACK (0,1) *          L CLAMP_BACK
+CLAMP_BACK ABsep *          R CHECK_B
+
+# Stop when B is zero
+CHECK_B 0 *                 R CHECK_B
+CHECK_B 1 *                 R DECREMENT_B
+CHECK_B EndB *              L RETURN
+
+# Subtract one from B
+DECREMENT_B (0,1) *         R DECREMENT_B
+DECREMENT_B EndB *          L DECREMENT_B_1
+DECREMENT_B_1 0 1           L DECREMENT_B_1
+DECREMENT_B_1 1 0           L TO_A
+
+# Go to the bit of A where the shift starts
+TO_A (0,1) *                L TO_A
+{to_a}
+
+# Shift every bit one place, a 0 is shifted in
+SHIFT_0 0 0                 {direction} SHIFT_0
+SHIFT_0 1 0                 {direction} SHIFT_1
+SHIFT_1 0 1                 {direction} SHIFT_0
+SHIFT_1 1 1                 {direction} SHIFT_1
+SHIFT_0 {shift_end}
+SHIFT_1 {shift_end}
+
+# Return to the middle of the tape
+RETURN (0,1,StartA,ABsep) * L RETURN
+RETURN Middle *             S END"
+    )
+}
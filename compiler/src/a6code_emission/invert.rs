@@ -0,0 +1,19 @@
+use super::prelude::*;
+
+pub fn invert_instructions(instruction_counter: &u32) -> Vec<String> {
+    let string = "
+# Start of the INVERT program
+START Middle *      R INVERT
+
+# Flip every bit of A
+INVERT StartA *     R INVERT
+INVERT 0 1          R INVERT
+INVERT 1 0          R INVERT
+INVERT ABsep *      L RETURN
+
+# Return to the middle of the tape
+RETURN (0,1,StartA) *   L RETURN
+RETURN Middle *         S END";
+
+    format_instructions(string.to_string(), *instruction_counter)
+}
@@ -1,34 +1,52 @@
 mod add;
+mod and;
+mod div;
 mod endfn;
+mod flipsign;
 mod helpers;
+mod invert;
 mod iszero;
 mod jnz;
 mod load;
 mod r#move;
 mod mul;
+mod neg;
 mod not;
+mod or;
+mod pop;
+mod print;
+mod push;
 mod set;
+mod shl;
+mod shr;
 mod store;
 mod sub;
 mod subsafe;
+mod xor;
 
 pub mod prelude {
     pub use super::add::add_instructions;
     pub use super::helpers::*;
     pub use super::iszero::iszero_instruction;
-    pub use super::load::load_instructions;
-    pub use super::store::store_instructions;
+    pub use super::load::{load_instructions, loadi_instructions};
+    pub use super::store::{store_instructions, storei_instructions};
     pub use super::*;
     pub use crate::a5code_generator::AssemblyInstruction;
+    pub use crate::diagnostics::Diagnostic;
+    pub use crate::options::CompileOptions;
     pub use crate::symbols::{symtou8, TapeSymbols};
 }
 
 use prelude::*;
 
 use self::{
-    endfn::endfn_instructions, jnz::jnz_instructions, mul::mul_instructions, not::not_instructions,
-    r#move::move_instruction, set::set_instructions, sub::sub_instructions,
-    subsafe::subsafe_instructions,
+    and::and_instructions, div::div_instructions, div::mod_instructions, endfn::endfn_instructions,
+    flipsign::flipsign_instructions, invert::invert_instructions, jnz::jnz_instructions,
+    mul::mul_instructions, neg::neg_instructions, not::not_instructions, or::or_instructions,
+    pop::pop_instructions, print::print_instructions, push::push_instructions,
+    r#move::move_instruction, set::set_instructions, shl::shl_instructions, shr::sar_instructions,
+    shr::shr_instructions, sub::sub_instructions, subsafe::subsafe_instructions,
+    xor::xor_instructions,
 };
 
 // Transform Assembly Instructions into Turing Machine Tape and Instructions.
@@ -37,10 +55,13 @@ use self::{
 // The Turing Machine Tape is a Vec of u8 numbers, and _ represents the blank symbol.
 
 // returns (tape, instructions)
-pub fn code_emission(assembly: Vec<AssemblyInstruction>) -> Vec<String> {
+pub fn code_emission(
+    assembly: Vec<AssemblyInstruction>,
+    options: &CompileOptions,
+) -> Result<Vec<String>, Diagnostic> {
     let mut instructions = Vec::new();
 
-    let mut instruction_counter = 1 as u32;
+    let mut instruction_counter: u32 = 1;
 
     let end_to_next_start = |i: u32| format!("\n{}END 5 5 S {}START", i - 1, i);
 
@@ -77,7 +98,7 @@ pub fn code_emission(assembly: Vec<AssemblyInstruction>) -> Vec<String> {
                 name,
                 total,
             } => {
-                let storage_address = address.replace("S", "").parse::<u32>().unwrap();
+                let storage_address = parse_storage_address(&address)?;
 
                 instructions.extend(header);
                 instructions.push(end_to_next_start(instruction_counter));
@@ -100,22 +121,27 @@ pub fn code_emission(assembly: Vec<AssemblyInstruction>) -> Vec<String> {
 
             // Set a value in the tape storage to a specific value
             AssemblyInstruction::SET { destination, value } => {
-                let storage_address = destination.replace("S", "").parse::<u32>().unwrap();
+                let storage_address = parse_storage_address(&destination)?;
+                if value > options.encode(-1) {
+                    return Err(Diagnostic::error(format!(
+                        "Value {value} for {destination} does not fit in {} bits",
+                        options.word_bits
+                    )));
+                }
 
-                let value_binary = format!("{:08b}", value);
+                let value_binary =
+                    format!("{:0width$b}", value, width = options.word_bits as usize);
                 let bool_vec = value_binary
                     .chars()
                     .map(|c| c == '1')
                     .collect::<Vec<bool>>();
 
-                let bool_array: [bool; 8] = bool_vec.try_into().unwrap();
-
                 instructions.extend(header);
                 instructions.push(end_to_next_start(instruction_counter));
                 instructions.extend(set_instructions(
                     &instruction_counter,
                     storage_address,
-                    bool_array,
+                    &bool_vec,
                 ));
 
                 instruction_counter += 1;
@@ -126,7 +152,7 @@ pub fn code_emission(assembly: Vec<AssemblyInstruction>) -> Vec<String> {
                 destination,
                 source,
             } => {
-                let storage_address = source.replace("S", "").parse::<u32>().unwrap();
+                let storage_address = parse_storage_address(&source)?;
                 let working_space = destination;
 
                 instructions.extend(header);
@@ -145,8 +171,8 @@ pub fn code_emission(assembly: Vec<AssemblyInstruction>) -> Vec<String> {
                 source,
                 destination,
             } => {
-                let source_address = source.replace("S", "").parse::<u32>().unwrap();
-                let destination_address = destination.replace("S", "").parse::<u32>().unwrap();
+                let source_address = parse_storage_address(&source)?;
+                let destination_address = parse_storage_address(&destination)?;
 
                 instructions.extend(header);
                 instructions.push(end_to_next_start(instruction_counter));
@@ -204,6 +230,114 @@ pub fn code_emission(assembly: Vec<AssemblyInstruction>) -> Vec<String> {
                 instruction_counter += 1;
             }
 
+            // Divide A by B, and store the quotient in A
+            AssemblyInstruction::DIV => {
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(div_instructions(&instruction_counter));
+
+                instruction_counter += 1;
+            }
+
+            // Divide A by B, and store the remainder in A
+            AssemblyInstruction::MOD => {
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(mod_instructions(&instruction_counter));
+
+                instruction_counter += 1;
+            }
+
+            // Bitwise AND of A and B, store the result in A
+            AssemblyInstruction::AND => {
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(and_instructions(&instruction_counter));
+
+                instruction_counter += 1;
+            }
+
+            // Bitwise OR of A and B, store the result in A
+            AssemblyInstruction::OR => {
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(or_instructions(&instruction_counter));
+
+                instruction_counter += 1;
+            }
+
+            // Bitwise XOR of A and B, store the result in A
+            AssemblyInstruction::XOR => {
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(xor_instructions(&instruction_counter));
+
+                instruction_counter += 1;
+            }
+
+            // Flip every bit in A
+            AssemblyInstruction::INVERT => {
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(invert_instructions(&instruction_counter));
+
+                instruction_counter += 1;
+            }
+
+            // Negate A in two's complement
+            AssemblyInstruction::NEG => {
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(neg_instructions(&instruction_counter));
+
+                instruction_counter += 1;
+            }
+
+            // Append A to the output after C
+            AssemblyInstruction::PRINT => {
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(print_instructions(&instruction_counter));
+
+                instruction_counter += 1;
+            }
+
+            // Shift A to the left by B bits
+            AssemblyInstruction::SHL => {
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(shl_instructions(&instruction_counter, options.word_bits));
+
+                instruction_counter += 1;
+            }
+
+            // Shift A to the right by B bits
+            AssemblyInstruction::SHR => {
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(shr_instructions(&instruction_counter, options.word_bits));
+
+                instruction_counter += 1;
+            }
+
+            // Shift A to the right by B bits, keeping the sign of A
+            AssemblyInstruction::SAR => {
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(sar_instructions(&instruction_counter, options.word_bits));
+
+                instruction_counter += 1;
+            }
+
+            // Flip the sign bits of A and B, so signed numbers can be compared as unsigned numbers
+            AssemblyInstruction::FLIPSIGN => {
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(flipsign_instructions(&instruction_counter));
+
+                instruction_counter += 1;
+            }
+
             // If the value in A is zero, put 0 in A, otherwise put 1 in A
             AssemblyInstruction::ISZERO => {
                 instructions.extend(header);
@@ -213,13 +347,39 @@ pub fn code_emission(assembly: Vec<AssemblyInstruction>) -> Vec<String> {
                 instruction_counter += 1;
             }
 
+            // Copy a value from the tape storage onto the top of the call stack
+            AssemblyInstruction::PUSH { source } => {
+                let storage_address = parse_storage_address(&source)?;
+
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(push_instructions(
+                    &instruction_counter,
+                    storage_address,
+                    options.word_bits,
+                ));
+
+                instruction_counter += 1;
+            }
+
+            // Move the value on top of the call stack into the tape storage, and remove it from the stack
+            AssemblyInstruction::POP { destination } => {
+                let storage_address = parse_storage_address(&destination)?;
+
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(pop_instructions(&instruction_counter, storage_address));
+
+                instruction_counter += 1;
+            }
+
             // STORE a value from the working area into the tape storage
             AssemblyInstruction::STORE {
                 destination,
                 source,
             } => {
                 let working_space = source;
-                let storage_address = destination.replace("S", "").parse::<u32>().unwrap();
+                let storage_address = parse_storage_address(&destination)?;
 
                 instructions.extend(header);
                 instructions.push(end_to_next_start(instruction_counter));
@@ -231,7 +391,28 @@ pub fn code_emission(assembly: Vec<AssemblyInstruction>) -> Vec<String> {
 
                 instruction_counter += 1;
             }
-            _ => panic!("Instruction {} not implemented", instruction.to_string()),
+
+            // Load the element with the index in B of an array into A
+            AssemblyInstruction::LOADI { source } => {
+                let storage_address = parse_storage_address(&source)?;
+
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(loadi_instructions(&instruction_counter, storage_address));
+
+                instruction_counter += 1;
+            }
+
+            // Store A in the element with the index in B of an array
+            AssemblyInstruction::STOREI { destination } => {
+                let storage_address = parse_storage_address(&destination)?;
+
+                instructions.extend(header);
+                instructions.push(end_to_next_start(instruction_counter));
+                instructions.extend(storei_instructions(&instruction_counter, storage_address));
+
+                instruction_counter += 1;
+            }
         }
     }
 
@@ -253,7 +434,15 @@ pub fn code_emission(assembly: Vec<AssemblyInstruction>) -> Vec<String> {
         instructions.insert(1, "\nSTART 5 5 S 0END".to_string());
     }
 
-    instructions
+    Ok(instructions)
+}
+
+// Parse a storage address such as "S3" into its cell number
+fn parse_storage_address(address: &str) -> Result<u32, Diagnostic> {
+    address
+        .strip_prefix('S')
+        .and_then(|number| number.parse::<u32>().ok())
+        .ok_or_else(|| Diagnostic::error(format!("Invalid storage address {address}")))
 }
 
 fn get_assembly_instruction_header(instruction: &str) -> Vec<String> {
@@ -0,0 +1,25 @@
+use super::prelude::*;
+
+// Negate A in two's complement: keep the bits up to and including the last 1, and flip the bits before it
+pub fn neg_instructions(instruction_counter: &u32) -> Vec<String> {
+    let string = "
+# Start of the NEG program, go to the end of A
+START Middle *          R TO_END
+TO_END (0,1,StartA) *   R TO_END
+TO_END ABsep *          L KEEP
+
+# Keep the zeros at the end of A, and the last 1
+KEEP 0 *                L KEEP
+KEEP 1 *                L FLIP
+KEEP StartA *           L RETURN
+
+# Flip every bit before the last 1
+FLIP 0 1                L FLIP
+FLIP 1 0                L FLIP
+FLIP StartA *           L RETURN
+
+# Return to the middle of the tape
+RETURN Middle *         S END";
+
+    format_instructions(string.to_string(), *instruction_counter)
+}
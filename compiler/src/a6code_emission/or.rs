@@ -0,0 +1,5 @@
+use super::prelude::*;
+
+pub fn or_instructions(instruction_counter: &u32) -> Vec<String> {
+    format_instructions(bitwise_operation("OR", [0, 1, 1, 1]), *instruction_counter)
+}
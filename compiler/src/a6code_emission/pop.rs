@@ -0,0 +1,49 @@
+use super::prelude::*;
+
+pub fn pop_instructions(instruction_counter: &u32, destination: u32) -> Vec<String> {
+    let to_des = go_to_storage(destination, "SET_TO_H0".to_string(), true);
+
+    let move_bits = move_bits("StMark", "R", "TO_STACK_TOP");
+
+    let string = format!(
+        "
+START Middle * L TO_{destination}_S1
+
+# Go to destination Sn
+{to_des}
+
+# Set all the values to H0 untill the start of Sn, and Mark end of Sn with StMark
+SET_TO_H0 (0,1) H0                          L SET_TO_H0
+SET_TO_H0 StSep *                           L TO_STACK_TOP
+
+# Go to the top of the call stack, at the far left of the tape
+TO_STACK_TOP (0,1,H0,H1,StSep) *            L TO_STACK_TOP
+TO_STACK_TOP _ *                            R TOP_CELL
+
+# Go to the end of the top cell
+TOP_CELL StSep *                            R TOP_CELL_END
+TOP_CELL_END (0,1,H0,H1) *                  R TOP_CELL_END
+TOP_CELL_END StSep *                        L COPY_VALUE
+
+# Copy the first non-H0/H1 value
+COPY_VALUE 0 H0                             S MOVE_ZERO
+COPY_VALUE 1 H1                             S MOVE_ONE
+COPY_VALUE (H0,H1) *                        L COPY_VALUE
+COPY_VALUE StSep _                          R CLEAR_CELL
+
+{move_bits}
+
+# Remove the top cell from the stack, its right seperator is the new top
+CLEAR_CELL (H0,H1) _                        R CLEAR_CELL
+CLEAR_CELL StSep *                          R RESET_STORAGE_MARKER
+# Reset StMark to StSep
+RESET_STORAGE_MARKER (0,1,StSep) *          R RESET_STORAGE_MARKER
+RESET_STORAGE_MARKER StMark StSep           R RETURN_TO_MIDDLE
+# Return to middle
+RETURN_TO_MIDDLE (0,1,StSep) *              R RETURN_TO_MIDDLE
+RETURN_TO_MIDDLE Middle Middle              S END
+"
+    );
+
+    format_instructions(string, *instruction_counter)
+}
@@ -0,0 +1,37 @@
+use super::prelude::*;
+
+// Append A to the output after C, every printed value is followed by a StSep.
+// The bits of A are marked one at a time and written at the first blank after the output.
+pub fn print_instructions(instruction_counter: &u32) -> Vec<String> {
+    let string = "
+# Start of the PRINT program, go to the first bit of A
+START Middle *              R NEXT_BIT
+
+# Mark the next bit of A that has not been printed
+NEXT_BIT (StartA,H0,H1) *   R NEXT_BIT
+NEXT_BIT 0 H0               R PRINT_0
+NEXT_BIT 1 H1               R PRINT_1
+NEXT_BIT ABsep *            R END_VALUE
+
+# Write the bit at the end of the output
+PRINT_0 (0,1,ABsep,EndB,EndC,StSep) *   R PRINT_0
+PRINT_0 _ 0                             L TO_A
+PRINT_1 (0,1,ABsep,EndB,EndC,StSep) *   R PRINT_1
+PRINT_1 _ 1                             L TO_A
+
+# Go back to the last marked bit of A
+TO_A (0,1,ABsep,EndB,EndC,StSep) *      L TO_A
+TO_A (H0,H1) *                          R NEXT_BIT
+
+# End the printed value with a separator
+END_VALUE (0,1,EndB,EndC,StSep) *       R END_VALUE
+END_VALUE _ StSep                       L RESTORE
+
+# Restore A, and return to the middle of the tape
+RESTORE (0,1,StartA,ABsep,EndB,EndC,StSep) *    L RESTORE
+RESTORE H0 0                                    L RESTORE
+RESTORE H1 1                                    L RESTORE
+RESTORE Middle *                                S END";
+
+    format_instructions(string.to_string(), *instruction_counter)
+}
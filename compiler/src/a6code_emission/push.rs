@@ -0,0 +1,57 @@
+use super::prelude::*;
+
+pub fn push_instructions(instruction_counter: &u32, source: u32, word_bits: u32) -> Vec<String> {
+    let to_source = go_to_storage(source, "COPY_VALUE".to_string(), false);
+
+    let mut new_cell = "".to_string();
+    for i in 1..=word_bits {
+        new_cell.push_str(&format!("\nNEW_CELL_{i} _ H0 L NEW_CELL_{}", i + 1));
+    }
+
+    let last = word_bits + 1;
+
+    let move_bits = move_bits("StMark", "L", "TO_MIDDLE_R");
+
+    let string = format!(
+        "
+START Middle * L TO_STACK_TOP
+
+# Go to the top of the call stack, at the far left of the tape
+TO_STACK_TOP (0,1,StSep) *                  L TO_STACK_TOP
+TO_STACK_TOP _ *                            R MARK_TOP
+
+# Mark the leftmost seperator, and add a new cell of H0 to the left of it
+MARK_TOP StSep StMark                       L NEW_CELL_1
+{new_cell}
+NEW_CELL_{last} _ StSep                          R TO_MIDDLE_R
+
+# Find the middle
+TO_MIDDLE_R (0,1,H0,H1,StSep,StMark) *      R TO_MIDDLE_R
+TO_MIDDLE_R Middle Middle                   L TO_{source}_S1
+
+# Go to source Sn
+{to_source}
+
+# Copy the first non-H0/H1 value
+COPY_VALUE 0 H0                             S MOVE_ZERO
+COPY_VALUE 1 H1                             S MOVE_ONE
+COPY_VALUE (H0,H1) *                        L COPY_VALUE
+COPY_VALUE (StSep,StMark) *                 R RESTORE_VALUE
+
+{move_bits}
+
+# Restore source, translate H0 to 0 and H1 to 1
+RESTORE_VALUE H0 0                          R RESTORE_VALUE
+RESTORE_VALUE H1 1                          R RESTORE_VALUE
+RESTORE_VALUE StSep *                       L RESET_STORAGE_MARKER
+# Reset StMark to StSep
+RESET_STORAGE_MARKER (0,1,StSep) *          L RESET_STORAGE_MARKER
+RESET_STORAGE_MARKER StMark StSep           S RETURN_TO_MIDDLE
+# Return to middle
+RETURN_TO_MIDDLE (0,1,StSep) *              R RETURN_TO_MIDDLE
+RETURN_TO_MIDDLE Middle Middle              S END
+"
+    );
+
+    format_instructions(string, *instruction_counter)
+}
@@ -0,0 +1,5 @@
+use super::prelude::*;
+
+pub fn shl_instructions(instruction_counter: &u32, word_bits: u32) -> Vec<String> {
+    format_instructions(shift_operation("L", false, word_bits), *instruction_counter)
+}
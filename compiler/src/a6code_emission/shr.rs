@@ -0,0 +1,10 @@
+use super::prelude::*;
+
+pub fn shr_instructions(instruction_counter: &u32, word_bits: u32) -> Vec<String> {
+    format_instructions(shift_operation("R", false, word_bits), *instruction_counter)
+}
+
+// Arithmetic shift to the right, the first bit of A is shifted in so the sign is kept
+pub fn sar_instructions(instruction_counter: &u32, word_bits: u32) -> Vec<String> {
+    format_instructions(shift_operation("R", true, word_bits), *instruction_counter)
+}
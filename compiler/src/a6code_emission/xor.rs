@@ -0,0 +1,5 @@
+use super::prelude::*;
+
+pub fn xor_instructions(instruction_counter: &u32) -> Vec<String> {
+    format_instructions(bitwise_operation("XOR", [0, 1, 1, 0]), *instruction_counter)
+}
@@ -1,25 +1,40 @@
 mod a1lexer;
 mod a2parser;
+mod a2semantic_analysis;
 mod a3intermediate_code_generator;
 mod a4optimization;
 mod a5code_generator;
 mod a6code_emission;
+mod diagnostics;
 mod interpreter;
+mod options;
+mod report;
+mod symbol_table;
 mod symbols;
 
 use a2parser::*;
+use a2semantic_analysis::analyze;
 use a3intermediate_code_generator::*;
 use a4optimization::optimize_tac;
 use a5code_generator::helpers::assemblyvec_to_string;
 use a5code_generator::*;
 use a6code_emission::code_emission;
+pub use diagnostics::{diagnostics_to_string, Diagnostic, Severity, Span};
+// Runs a Turing program without compiling it, for the tests of the command line tool
+#[doc(hidden)]
+pub use interpreter::try_run_code;
+pub use options::CompileOptions;
+pub use report::RunReport;
 use serde::Serialize;
+use std::time::Instant;
 use symbols::{symtou8, TapeSymbols};
+use turing_vm::{Breakpoint, Machine};
 
 #[derive(Clone, Serialize)]
 pub struct Token {
     pub type_: String,
     pub value: String,
+    pub span: Span,
 }
 
 pub struct CompilationResult {
@@ -33,11 +48,17 @@ pub struct CompilationResult {
     pub optimized_tac_string: String,
     pub assembly: Vec<AssemblyInstruction>,
     pub assembly_string: String,
-    pub turing_program: String,
+    pub turing_program: String, // the tape starts with every input set to 0
+    pub params: Vec<String>,    // parameters of main, they are read from the tape
+    pub storage_size: i32,
+    pub storage: Vec<StorageLocation>, // where every variable is stored
+    pub options: CompileOptions,
+    pub diagnostics: Vec<Diagnostic>, // warnings, compilation stops at the first error
 }
 
+// Tokens used for syntax highlighting, parts of the code that could not be tokenized are skipped
 pub fn lexer(code: String) -> Vec<Token> {
-    let tokens = a1lexer::lexer(code);
+    let (tokens, _) = a1lexer::lexer(code);
 
     // transform tokens from Vec<a1lexer::Token> to Vec<Token>
     let tokens = tokens
@@ -45,6 +66,7 @@ pub fn lexer(code: String) -> Vec<Token> {
         .map(|token| Token {
             type_: format!("{:?}", token.type_),
             value: token.value.clone(),
+            span: token.span,
         })
         .collect();
     tokens
@@ -56,31 +78,67 @@ pub fn lexer_json(code: String) -> String {
 }
 
 pub fn compile(code: &str) -> CompilationResult {
-    let tokens = a1lexer::lexer(code.to_string());
+    compile_with_options(code, &CompileOptions::default())
+}
+
+pub fn compile_with_options(code: &str, options: &CompileOptions) -> CompilationResult {
+    match try_compile_with_options(code, options) {
+        Ok(result) => result,
+        Err(diagnostics) => panic!("{}", diagnostics_to_string(&diagnostics)),
+    }
+}
+
+pub fn try_compile(code: &str) -> Result<CompilationResult, Vec<Diagnostic>> {
+    try_compile_with_options(code, &CompileOptions::default())
+}
+
+pub fn try_compile_with_options(
+    code: &str,
+    options: &CompileOptions,
+) -> Result<CompilationResult, Vec<Diagnostic>> {
+    if !(1..=64).contains(&options.word_bits) {
+        return Err(vec![Diagnostic::error(format!(
+            "Word width must be between 1 and 64 bits, got {}",
+            options.word_bits
+        ))]);
+    }
+
+    let (tokens, diagnostics) = a1lexer::lexer(code.to_string());
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
     let tokens_string = a1lexer::tokens_to_string(tokens.clone());
 
-    let ast = a2parser::parser(tokens.clone());
+    let ast = a2parser::parser(tokens.clone()).map_err(|d| vec![d])?;
     let ast_string = serde_json::to_string_pretty(&ast).unwrap();
 
-    let tac = tac_generator(&ast);
+    let (diagnostics, names) = analyze(&ast, options);
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return Err(diagnostics);
+    }
+
+    let tac = tac_generator(&ast, &names).map_err(|d| vec![d])?;
     let tac_string = tacvec_to_string(&tac);
 
     let optimized_tac = optimize_tac(tac.clone());
     let optimized_tac_string = tacvec_to_string(&optimized_tac);
 
-    let (assembly, storage_size) = code_generator(optimized_tac.clone());
+    let (assembly, storage_size, storage) =
+        code_generator(optimized_tac.clone(), options).map_err(|d| vec![d])?;
     let assembly_string = assemblyvec_to_string(assembly.clone());
 
-    let mut turing_instructions: Vec<String> = code_emission(assembly.clone());
-    let tape = create_tape(storage_size);
+    let mut turing_instructions: Vec<String> =
+        code_emission(assembly.clone(), options).map_err(|d| vec![d])?;
+    let params = main_params(&optimized_tac);
+    let tape = create_tape(storage_size, options.word_bits, &vec![0; params.len()]);
 
     // embed original program into turing code for debugging
     turing_instructions.insert(0, "\n# Original program".to_string());
-    turing_instructions.insert(1, ("\n".to_string() + &code).replace("\n", "\n#program "));
+    turing_instructions.insert(1, ("\n".to_string() + code).replace("\n", "\n#program "));
 
     let turing_contents = format!("{}\n{}", tape, turing_instructions.join("\n"));
 
-    CompilationResult {
+    Ok(CompilationResult {
         tokens,
         tokens_string,
         ast,
@@ -92,15 +150,120 @@ pub fn compile(code: &str) -> CompilationResult {
         assembly,
         assembly_string,
         turing_program: turing_contents,
+        params,
+        storage_size,
+        storage,
+        options: *options,
+        diagnostics,
+    })
+}
+
+impl CompilationResult {
+    // The turing program with the inputs written into the parameters of main,
+    // so the same compiled program can be run on many inputs
+    pub fn turing_program_with_inputs(&self, inputs: &[u8]) -> Result<String, Diagnostic> {
+        let inputs = self.options.encode_inputs(self.params.len(), inputs)?;
+        let tape = create_tape(self.storage_size, self.options.word_bits, &inputs);
+
+        // the first line of the program is the tape
+        let (_, instructions) = self.turing_program.split_once('\n').unwrap();
+        Ok(format!("{}\n{}", tape, instructions))
     }
+
+    // Run the program on the inputs and return the number in A, negative in signed mode
+    pub fn run(&self, inputs: &[u8]) -> Result<i128, Diagnostic> {
+        Ok(self.run_with_output(inputs)?.1)
+    }
+
+    // Run the program on the inputs and return the printed values and the number in A,
+    // decoded like the words in RunReport
+    pub fn run_with_output(&self, inputs: &[u8]) -> Result<(Vec<i128>, i128), Diagnostic> {
+        let turing_program = self.turing_program_with_inputs(inputs)?;
+        let (output, a) = interpreter::try_run_code(&turing_program).map_err(Diagnostic::error)?;
+        let output = output
+            .into_iter()
+            .map(|value| self.options.decode(value))
+            .collect();
+        Ok((output, self.options.decode(a)))
+    }
+
+    // Run the program on the inputs for at most max_steps steps, and report the state of the tape
+    pub fn run_report(&self, inputs: &[u8], max_steps: u64) -> Result<RunReport, Diagnostic> {
+        let turing_program = self.turing_program_with_inputs(inputs)?;
+        let mut machine =
+            Machine::load(&turing_program).map_err(|error| Diagnostic::error(error.to_string()))?;
+
+        let start = Instant::now();
+        let outcome = machine.run_with_limit(max_steps, None);
+        Ok(RunReport::new(
+            &machine,
+            outcome,
+            start.elapsed(),
+            &self.storage,
+            &self.options,
+        ))
+    }
+
+    // A breakpoint for Machine::run_until on a new value of a variable.
+    // Variables are named like in RunReport::storage, such as global::x or global::xs[2] for an array element.
+    pub fn watch(&self, name: &str) -> Option<Breakpoint> {
+        let (name, index) = match name.strip_suffix(']').and_then(|name| name.split_once('[')) {
+            Some((name, index)) => (name, index.parse().ok()?),
+            None => (name, 0),
+        };
+        let location = self
+            .storage
+            .iter()
+            .find(|location| location.name == name && index < location.size)?;
+
+        // The storage does not move while the program runs, so the middle stays where it is on the first tape
+        let (tape, _) = self.turing_program.split_once('\n')?;
+        let middle = symtou8(TapeSymbols::Middle).to_string();
+        let middle = tape
+            .split_whitespace()
+            .position(|cell| cell.trim_start_matches('!') == middle)?;
+
+        let (start, end) = storage_cells(
+            middle as i64,
+            location.address + index,
+            self.options.word_bits,
+        );
+        Some(Breakpoint::Watch { start, end })
+    }
+}
+
+pub fn compile_and_run(code: &str, inputs: &[u8]) -> Result<i128, Vec<Diagnostic>> {
+    try_compile(code)?.run(inputs).map_err(|d| vec![d])
 }
 
-pub fn create_tape(storage_size: i32) -> String {
+// The coordinates of the first cell of S{address} and of the cell after its last cell, on a tape with the middle at middle.
+// S1 is left of the middle, and every cell is followed by a separator: ... [S2] 6 [S1] 6 Middle
+pub fn storage_cells(middle: i64, address: usize, word_bits: u32) -> (i64, i64) {
+    let width = word_bits as i64;
+    let end = middle - 1 - (address as i64 - 1) * (width + 1);
+    (end - width, end)
+}
+
+// Inputs are written into S2, S3, ..., the cells of the parameters of main, ret is in S1
+pub fn create_tape(storage_size: i32, word_bits: u32, inputs: &[u64]) -> String {
+    let word = "0 ".repeat(word_bits as usize);
+    let word = word.trim();
+
     let mut tape_storage_vec = Vec::new();
 
-    for _ in 0..storage_size {
-        let binary = "0 ".repeat(8);
-        tape_storage_vec.push(binary.trim().to_string());
+    // the first cell on the tape is the one with the largest address
+    for address in (1..=storage_size).rev() {
+        match usize::try_from(address - 2)
+            .ok()
+            .and_then(|i| inputs.get(i))
+        {
+            Some(input) => {
+                let bits = format!("{:0width$b}", input, width = word_bits as usize);
+                let bits: Vec<String> = bits.chars().map(|bit| bit.to_string()).collect();
+                tape_storage_vec.push(bits.join(" "));
+            }
+            None => tape_storage_vec.push(word.to_string()),
+        }
     }
     let tape_storage = format!("6 {} 6", tape_storage_vec.join(" 6 "));
 
@@ -110,9 +273,7 @@ pub fn create_tape(storage_size: i32) -> String {
     let end_c = symtou8(TapeSymbols::EndC).to_string();
     let middle = symtou8(TapeSymbols::Middle).to_string();
 
-    let tape_working_area = format!(
-        "{start_a} 0 0 0 0 0 0 0 0 {end_a} 0 0 0 0 0 0 0 0 {end_b} 0 0 0 0 0 0 0 0 {end_c}"
-    );
+    let tape_working_area = format!("{start_a} {word} {end_a} {word} {end_b} {word} {end_c}");
 
     format!("{tape_storage} !{middle} {tape_working_area}")
 }
@@ -1,77 +1,266 @@
-use std::fs;
-
-mod a1lexer;
-mod a2parser;
-mod a3intermediate_code_generator;
-mod a4optimization;
-mod a5code_generator;
-mod a6code_emission;
-mod interpreter;
-mod symbols;
-
-use a1lexer::*;
-use a2parser::*;
-use a3intermediate_code_generator::*;
-use a4optimization::optimize_tac;
-use a5code_generator::helpers::assemblyvec_to_string;
-use a5code_generator::*;
-use interpreter::run_code;
-
-use crate::a6code_emission::code_emission;
+use std::{
+    env, fs,
+    io::{self, Read},
+    process,
+};
+
+use turing_compiler::{diagnostics_to_string, CompilationResult, CompileOptions};
+use turing_vm::RunOutcome;
+
+const USAGE: &str = "Usage:
+    turing_compiler build <file> [-o <output>] [options]
+    turing_compiler emit --stage <stage> <file> [-o <output>] [options]
+    turing_compiler run <file> [options]
+
+<file> is the program, or - to read it from stdin.
+The stages are tokens, ast, tac, opt-tac, asm and tm.
+
+Options:
+    --input <a,b,...>   inputs for the parameters of main
+    --bits <n>          number of bits in a word, 8 by default
+    --signed            numbers are signed in two's complement
+    --max-steps <n>     stop running after n steps
+    --report            print the state of the tape after running as JSON";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Build,
+    Emit(Stage),
+    Run,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Tokens,
+    Ast,
+    Tac,
+    OptimizedTac,
+    Assembly,
+    TuringProgram,
+}
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    command: Command,
+    file: String,
+    output: Option<String>,
+    inputs: Vec<u8>,
+    options: CompileOptions,
+    max_steps: u64,
+    report: bool,
+}
 
 fn main() {
-    let source_code = fs::read_to_string("input.txt").unwrap();
-    let turing_code = compile_debug(source_code);
-    fs::write("output.txt", &turing_code).unwrap();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{message}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    if let Err(message) = execute(&args) {
+        eprintln!("{message}");
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut args = args.iter();
+
+    let mut command = match args.next().map(String::as_str) {
+        Some("build") => Command::Build,
+        Some("emit") => Command::Emit(Stage::TuringProgram),
+        Some("run") => Command::Run,
+        Some(command) => return Err(format!("Unknown command '{command}'")),
+        None => return Err("Missing command".to_string()),
+    };
+    let mut has_stage = false;
+    let mut file = None;
+    let mut output = None;
+    let mut inputs = Vec::new();
+    let mut options = CompileOptions::default();
+    let mut max_steps = u64::MAX;
+    let mut report = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--stage" if matches!(command, Command::Emit(_)) => {
+                command = Command::Emit(parse_stage(value()?)?);
+                has_stage = true;
+            }
+            "-o" if command != Command::Run => output = Some(value()?.clone()),
+            "--input" => inputs = parse_inputs(value()?)?,
+            "--bits" => {
+                let bits = value()?;
+                options.word_bits = bits
+                    .parse()
+                    .map_err(|_| format!("Invalid number of bits '{bits}'"))?;
+            }
+            "--signed" => options.signed = true,
+            "--max-steps" if command == Command::Run => {
+                let steps = value()?;
+                max_steps = steps
+                    .parse()
+                    .map_err(|_| format!("Invalid number of steps '{steps}'"))?;
+            }
+            "--report" if command == Command::Run => report = true,
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("Unknown option '{arg}'"));
+            }
+            _ if file.is_none() => file = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument '{arg}'")),
+        }
+    }
+
+    if matches!(command, Command::Emit(_)) && !has_stage {
+        return Err("emit needs a --stage".to_string());
+    }
+    let file = file.ok_or("Missing the program file, use - to read it from stdin")?;
+
+    Ok(Args {
+        command,
+        file,
+        output,
+        inputs,
+        options,
+        max_steps,
+        report,
+    })
 }
 
-fn compile_debug(code: String) -> String {
-    let tokens = lexer(code.clone());
-    let tokens_string = tokens_to_string(tokens.clone());
-    fs::write("compiler_steps/step1_tokens.txt", &tokens_string).unwrap();
+fn parse_stage(stage: &str) -> Result<Stage, String> {
+    match stage {
+        "tokens" => Ok(Stage::Tokens),
+        "ast" => Ok(Stage::Ast),
+        "tac" => Ok(Stage::Tac),
+        "opt-tac" => Ok(Stage::OptimizedTac),
+        "asm" => Ok(Stage::Assembly),
+        "tm" => Ok(Stage::TuringProgram),
+        _ => Err(format!("Unknown stage '{stage}'")),
+    }
+}
 
-    let ast = parser(tokens);
-    let ast_json = serde_json::to_string_pretty(&ast).unwrap();
-    fs::write("compiler_steps/step2_ast.json", format!("{}", &ast_json)).unwrap();
+// Inputs for the parameters of main, given as `--input 3,7`
+fn parse_inputs(inputs: &str) -> Result<Vec<u8>, String> {
+    inputs
+        .split(',')
+        .map(|input| {
+            input
+                .trim()
+                .parse::<u8>()
+                .map_err(|_| format!("Input '{input}' is not a number from 0 to 255"))
+        })
+        .collect()
+}
 
-    let tac = tac_generator(&ast);
-    let tac_string = tacvec_to_string(&tac);
-    fs::write("compiler_steps/step3_tac.txt", &tac_string).unwrap();
+fn execute(args: &Args) -> Result<(), String> {
+    let code = if args.file == "-" {
+        let mut code = String::new();
+        io::stdin()
+            .read_to_string(&mut code)
+            .map_err(|error| format!("Could not read stdin: {error}"))?;
+        code
+    } else {
+        fs::read_to_string(&args.file)
+            .map_err(|error| format!("Could not read {}: {error}", args.file))?
+    };
 
-    let optimized_tac = optimize_tac(tac.clone());
-    let optimized_tac_string = tacvec_to_string(&optimized_tac);
-    fs::write(
-        "compiler_steps/step4_optimized_tac.txt",
-        &optimized_tac_string,
-    )
-    .unwrap();
+    let result = turing_compiler::try_compile_with_options(&code, &args.options)
+        .map_err(|diagnostics| diagnostics_to_string(&diagnostics))?;
+    if !result.diagnostics.is_empty() {
+        eprintln!("{}", diagnostics_to_string(&result.diagnostics));
+    }
 
-    let (assembly, storage_size) = code_generator(optimized_tac.clone());
-    let assembly_string = assemblyvec_to_string(assembly.clone());
-    fs::write("compiler_steps/step5_assembly.txt", &assembly_string).unwrap();
+    let contents = match args.command {
+        Command::Build => stage_output(&result, Stage::TuringProgram, &args.inputs)?,
+        Command::Emit(stage) => stage_output(&result, stage, &args.inputs)?,
+        Command::Run => {
+            let report = result
+                .run_report(&args.inputs, args.max_steps)
+                .map_err(|diagnostic| diagnostic.to_string())?;
+            if args.report {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            }
+            if report.outcome != RunOutcome::Halted {
+                return Err(format!(
+                    "The program did not halt after {} steps, {}",
+                    report.steps, report.outcome
+                ));
+            }
 
-    let mut turing_code = code_emission(assembly);
+            // the printed values, and the return value on the last line
+            if !args.report {
+                for printed in report.output {
+                    println!("{printed}");
+                }
+                match report.a {
+                    Some(a) => println!("{a}"),
+                    None => return Err("The program halted without a number in A".to_string()),
+                }
+            }
+            return Ok(());
+        }
+    };
 
-    // embed original program into turing code for debugging
-    turing_code.insert(0, "\n# Original program".to_string());
-    turing_code.insert(1, ("\n".to_string() + &code).replace("\n", "\n#program "));
+    write_output(&args.output, &contents)
+}
 
-    let tape = turing_compiler::create_tape(storage_size);
+// The result of one stage of the compiler
+fn stage_output(result: &CompilationResult, stage: Stage, inputs: &[u8]) -> Result<String, String> {
+    Ok(match stage {
+        Stage::Tokens => result.tokens_string.clone(),
+        Stage::Ast => result.ast_string.clone(),
+        Stage::Tac => result.tac_string.clone(),
+        Stage::OptimizedTac => result.optimized_tac_string.clone(),
+        Stage::Assembly => result.assembly_string.clone(),
+        // without inputs, the parameters of main start at 0
+        Stage::TuringProgram if inputs.is_empty() => result.turing_program.clone(),
+        Stage::TuringProgram => result
+            .turing_program_with_inputs(inputs)
+            .map_err(|diagnostic| diagnostic.to_string())?,
+    })
+}
 
-    let turing_contents = format!("{}\n{}", tape, turing_code.join("\n"));
-    fs::write("compiler_steps/step6_turingcode.txt", &turing_contents).unwrap();
-    turing_contents
+// Write to the file given with -o, or to stdout
+fn write_output(output: &Option<String>, contents: &str) -> Result<(), String> {
+    match output {
+        Some(path) => {
+            fs::write(path, contents).map_err(|error| format!("Could not write {path}: {error}"))
+        }
+        None => {
+            println!("{contents}");
+            Ok(())
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use turing_compiler::try_run_code;
+
+    // The helpers panic if the program does not halt
+    fn run_code(code: String) -> u64 {
+        run_code_with_output(code).1
+    }
+
+    // Run the code, return the printed values and the number in A
+    fn run_code_with_output(code: String) -> (Vec<u64>, u64) {
+        try_run_code(&code).unwrap_or_else(|message| panic!("{message}"))
+    }
+
     #[test]
     fn math1() {
         let code = "
 let a = (4 + 2) * 3 + 6 - 20;
 return a;";
         let turing_code = turing_compiler::compile(code).turing_program;
-        let result = crate::run_code(turing_code);
+        let result = run_code(turing_code);
         assert_eq!(result, 4);
     }
 
@@ -82,7 +271,7 @@ let a = (22 - 8) * 4 + 5;
 let b = a == 61;
 return b;";
         let turing_code = turing_compiler::compile(code).turing_program;
-        let result = crate::run_code(turing_code);
+        let result = run_code(turing_code);
         assert_eq!(result, 1);
     }
 
@@ -99,7 +288,7 @@ if b {
 };
 return c;";
         let turing_code = turing_compiler::compile(code).turing_program;
-        let result = crate::run_code(turing_code);
+        let result = run_code(turing_code);
         assert_eq!(result, 12);
     }
 
@@ -114,7 +303,7 @@ while (a > 0) {
 };
 return b;";
         let turing_code = turing_compiler::compile(code).turing_program;
-        let result = crate::run_code(turing_code);
+        let result = run_code(turing_code);
         assert_eq!(result, 32);
     }
 
@@ -131,10 +320,126 @@ fn main() {
     return e;
 };";
         let turing_code = turing_compiler::compile(code).turing_program;
-        let result = crate::run_code(turing_code);
+        let result = run_code(turing_code);
         assert_eq!(result, 14);
     }
 
+    #[test]
+    fn ifelse_multiple_statements() {
+        let code = "
+let a = 3;
+let b = 0;
+let c = 0;
+if a == 3 {
+    b = 4;
+    c = b * 2;
+} else {
+    b = 1;
+    c = 1;
+};
+return b + c;";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 12);
+    }
+
+    #[test]
+    fn nested_ifelse() {
+        let code = "
+let a = 7;
+let b = 0;
+if a > 5 {
+    if a < 7 {
+        b = 1;
+    } else {
+        b = 2;
+        if a == 7 {
+            b = b + 10;
+        };
+    };
+} else {
+    b = 3;
+};
+return b;";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 12);
+    }
+
+    #[test]
+    fn else_if_chain() {
+        let code = "
+let a = 5;
+let b = 0;
+if a < 2 {
+    b = 1;
+} else if a < 4 {
+    b = 2;
+} else if a < 6 {
+    b = 3;
+} else {
+    b = 4;
+};
+return b;";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 3);
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        let code = "
+let a = 4
+return a;";
+        let diagnostics = turing_compiler::try_compile(code).err().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Expected semicolon at the end of statement, found 'return'"
+        );
+        let span = diagnostics[0].span.unwrap();
+        assert_eq!((span.line, span.column), (3, 1));
+    }
+
+    #[test]
+    fn unexpected_end() {
+        let code = "
+while (a > 0) {
+    a = a - 1;";
+        let diagnostics = turing_compiler::try_compile(code).err().unwrap();
+        assert_eq!(diagnostics[0].message, "Expected '}' at the end of body");
+        let span = diagnostics[0].span.unwrap();
+        assert_eq!((span.line, span.column), (3, 15));
+    }
+
+    #[test]
+    fn no_whitespace_and_comments() {
+        let code = "
+// Comments are ignored
+let a=(4+2)*3; /* even
+when they span multiple lines */
+let b=a==18;
+if b {a=0x1F+0b1010;};
+return a;";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 41);
+    }
+
+    #[test]
+    fn unexpected_character() {
+        let code = "
+let a = 4 @ 2;
+/* not closed";
+        let diagnostics = turing_compiler::try_compile(code).err().unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "Unexpected character '@'");
+        let span = diagnostics[0].span.unwrap();
+        assert_eq!((span.line, span.column, span.start), (2, 11, 11));
+        assert_eq!(diagnostics[1].message, "Unterminated block comment");
+    }
+
+    #[test]
     fn fibonacci() {
         let code = "
 fn fibonacci(n) {
@@ -149,7 +454,785 @@ fn fibonacci(n) {
 let a = fibonacci(10);
 return a;";
         let turing_code = turing_compiler::compile(code).turing_program;
-        let result = crate::run_code(turing_code);
+        let result = run_code(turing_code);
+        assert_eq!(result, 55);
+    }
+
+    #[test]
+    fn recursion_with_global() {
+        let code = "
+let count = 0;
+fn count_down(n) {
+    if n == 0 {
+        return 0;
+    };
+    count = count + 1;
+    count_down(n - 1);
+    return count;
+};
+return count_down(3);";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 3);
+    }
+
+    #[test]
+    fn recursion_with_logical_result() {
+        // The result of || is assigned on both branches before the call, and read after it
+        let code = "
+fn f(n) {
+    if n == 0 {
+        return 0;
+    };
+    return (n > 2 || n == 1) + f(n - 1);
+};
+return f(4);";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        assert_eq!(run_code(turing_code), 3);
+    }
+
+    #[test]
+    fn recursion_with_locals() {
+        let code = "
+fn sum_to(n) {
+    if n == 0 {
+        return 0;
+    };
+    let rest = sum_to(n - 1);
+    return rest + n;
+};
+return sum_to(10);";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
         assert_eq!(result, 55);
     }
+
+    #[test]
+    fn mutual_recursion() {
+        let code = "
+fn is_even(n) {
+    if n == 0 {
+        return 1;
+    };
+    return is_odd(n - 1);
+};
+fn is_odd(n) {
+    if n == 0 {
+        return 0;
+    };
+    return is_even(n - 1);
+};
+let a = is_even(6);
+let b = is_odd(6);
+return a * 2 + b;";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 2);
+    }
+
+    #[test]
+    fn function_locals() {
+        let code = "
+let a = 5;
+fn add(b, c) {
+    let a = b + c;
+    return a;
+};
+let d = add(1, 2);
+return a + d;";
+        let result = turing_compiler::compile(code);
+        assert_eq!(run_code(result.turing_program), 8);
+        assert_eq!(result.diagnostics.len(), 1);
+        assert_eq!(
+            result.diagnostics[0].message,
+            "Declaration of 'a' shadows an earlier declaration"
+        );
+        let span = result.diagnostics[0].span.unwrap();
+        assert_eq!((span.line, span.column), (4, 9));
+    }
+
+    #[test]
+    fn block_scope() {
+        let code = "
+let a = 1;
+if a == 1 {
+    let a = 10;
+    a = a + 1;
+};
+return a;";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn undeclared_variable() {
+        let code = "
+fn f(n) {
+    let x = n;
+    return x;
+};
+x = 3;
+return f(y);";
+        let diagnostics = turing_compiler::try_compile(code).err().unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "Use of undeclared variable 'x'");
+        let span = diagnostics[0].span.unwrap();
+        assert_eq!((span.line, span.column), (6, 1));
+        assert_eq!(diagnostics[1].message, "Use of undeclared variable 'y'");
+    }
+
+    #[test]
+    fn semantic_errors() {
+        let code = "
+fn add(a, b) {
+    return a + b;
+};
+fn main() {
+    let c = add(1);
+    let d = sub(c, 300);
+    return d;
+};
+return 1;";
+        let diagnostics = turing_compiler::try_compile(code).err().unwrap();
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Function 'add' expects 2 arguments, but got 1",
+                "Call to undefined function 'sub'",
+                "Constant 300 does not fit in 8 bits (0 to 255)",
+                "Return outside of a function, the program starts at main",
+            ]
+        );
+        let span = diagnostics[2].span.unwrap();
+        assert_eq!((span.line, span.column), (7, 20));
+    }
+
+    #[test]
+    fn division() {
+        let code = "
+let a = 200;
+let b = 7;
+let q = a / b;
+let r = a % b;
+return q + r;";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 32);
+    }
+
+    #[test]
+    fn division_edge_cases() {
+        let cases = [
+            ("255 / 1", 255),
+            ("255 / 255", 1),
+            ("254 / 255", 0),
+            ("255 % 16", 15),
+            ("9 / 3", 3),
+            ("9 % 3", 0),
+            ("0 / 5", 0),
+            ("5 / 0", 255),
+            ("5 % 0", 5),
+            ("200 / 129", 1),
+            ("200 % 129", 71),
+        ];
+        for (expression, expected) in cases {
+            let code = format!("let a = {expression};\nreturn a;");
+            let turing_code = turing_compiler::compile(&code).turing_program;
+            let result = run_code(turing_code);
+            assert_eq!(result, expected, "{expression}");
+        }
+    }
+
+    #[test]
+    fn comparison_operators() {
+        for operator in ["==", "!=", "<", "<=", ">", ">="] {
+            for (a, b) in [(0u8, 0u8), (0, 255), (255, 0), (255, 255)] {
+                let expected = match operator {
+                    "==" => a == b,
+                    "!=" => a != b,
+                    "<" => a < b,
+                    "<=" => a <= b,
+                    ">" => a > b,
+                    _ => a >= b,
+                };
+                let code = format!("let a = {a};\nlet b = {b};\nreturn a {operator} b;");
+                let turing_code = turing_compiler::compile(&code).turing_program;
+                let result = run_code(turing_code);
+                assert_eq!(result, expected as u64, "{a} {operator} {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn comparison_precedence() {
+        let code = "
+let a = 3;
+let b = 0;
+if a + 1 >= 4 {
+    b = b + 1;
+};
+if a * 2 != 6 {
+    b = b + 10;
+};
+if a <= a - 1 + 1 {
+    b = b + 100;
+};
+return b;";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 101);
+    }
+
+    #[test]
+    fn logical_operators() {
+        let code = "
+let a = 5;
+let b = 0;
+let c = 0;
+if a > 0 && b < 10 {
+    c = c + 1;
+};
+if a == 0 || !(b == 0) {
+    c = c + 10;
+};
+while c < 5 && !(a == 0) {
+    c = c + 1;
+    a = a - 1;
+};
+let d = a > 0 || b > 0;
+let e = b == 0 && a >= 1;
+let f = !b;
+return c * 10 + d + e * 2 + f * 4;";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 57);
+    }
+
+    #[test]
+    fn short_circuit() {
+        let code = "
+let b = 0;
+fn set_b() {
+    b = b + 1;
+    return 1;
+};
+let a = 0 && set_b();
+let c = 1 || set_b();
+let d = 1 && set_b();
+return a + c * 2 + d * 4 + b * 8;";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 14);
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        let cases = [
+            ("0b1100 & 0b1010", 0b1000),
+            ("0b1100 | 0b1010", 0b1110),
+            ("0b1100 ^ 0b1010", 0b0110),
+            ("~0b1100", 0b11110011),
+            ("255 & 255", 255),
+            ("0 | 0", 0),
+            ("1 << 7", 128),
+            ("0b11000011 << 2", 0b00001100),
+            ("255 >> 4", 15),
+            ("0b10000001 >> 0", 0b10000001),
+            ("1 << 9", 0),
+            ("1 << 8", 0),
+            ("255 << 200", 0),
+            ("255 >> 255", 0),
+            ("0b10000000 >> 7", 1),
+            ("1 + 2 << 1 | 1", 7),
+            ("6 & 3 == 2", 1),
+        ];
+        for (expression, expected) in cases {
+            let code = format!("let a = {expression};\nreturn a;");
+            let turing_code = turing_compiler::compile(&code).turing_program;
+            let result = run_code(turing_code);
+            assert_eq!(result, expected, "{expression}");
+        }
+    }
+
+    #[test]
+    fn multiplication_by_power_of_two() {
+        let code = "
+let a = 37;
+let b = a * 8;
+let c = 4 * a;
+return b + c;";
+        let result = turing_compiler::compile(code);
+        assert!(!result.optimized_tac_string.contains('*'));
+        assert_eq!(run_code(result.turing_program), 188);
+    }
+
+    #[test]
+    fn multiplication_by_power_of_two_with_arrays() {
+        let code = "
+let xs = [4; 3];
+let i = 1;
+xs[i] = xs[0] * 4;
+xs[2] = 2 * xs[i];
+return xs[0] + xs[1] + xs[2];";
+        let result = turing_compiler::compile(code);
+        assert_eq!(run_code(result.turing_program), 4 + 16 + 32);
+    }
+
+    #[test]
+    fn word_width_16() {
+        let code = "
+let a = 300;
+let b = a * 200;
+return b / 7 + b % 7;";
+        let options = turing_compiler::CompileOptions {
+            word_bits: 16,
+            ..Default::default()
+        };
+        let result = turing_compiler::compile_with_options(code, &options);
+        assert_eq!(run_code(result.turing_program), 8574);
+
+        let diagnostics = turing_compiler::try_compile_with_options("return 70000;", &options)
+            .err()
+            .unwrap();
+        assert_eq!(
+            diagnostics[0].message,
+            "Constant 70000 does not fit in 16 bits (0 to 65535)"
+        );
+    }
+
+    #[test]
+    fn call_sites_in_narrow_words() {
+        let options = turing_compiler::CompileOptions {
+            word_bits: 2,
+            ..Default::default()
+        };
+        let code = "
+fn one() {
+    return 1;
+};
+fn main() {
+    return one() + one() + one() - one();
+};";
+        let result = turing_compiler::compile_with_options(code, &options);
+        assert_eq!(run_code(result.turing_program), 2);
+
+        let code = code.replace("- one()", "- one() + one()");
+        let diagnostics = turing_compiler::try_compile_with_options(&code, &options)
+            .err()
+            .unwrap();
+        assert_eq!(
+            diagnostics[0].message,
+            "Function 'one' has more than 4 call sites, which do not fit in 2 bits"
+        );
+    }
+
+    #[test]
+    fn word_width_32() {
+        let code = "
+fn double(x) {
+    return x + x;
+};
+fn main() {
+    let a = 1000000;
+    return double(a) - 1;
+};";
+        let options = turing_compiler::CompileOptions {
+            word_bits: 32,
+            ..Default::default()
+        };
+        let result = turing_compiler::compile_with_options(code, &options);
+        assert_eq!(run_code(result.turing_program), 1999999);
+
+        let options = turing_compiler::CompileOptions {
+            word_bits: 0,
+            ..Default::default()
+        };
+        assert!(turing_compiler::try_compile_with_options(code, &options).is_err());
+    }
+
+    #[test]
+    fn signed_arithmetic() {
+        let options = turing_compiler::CompileOptions {
+            signed: true,
+            ..Default::default()
+        };
+        let cases = [
+            ("-5", -5),
+            ("-128", -128),
+            ("3 - 10", -7),
+            ("-(2 + 3) * 4", -20),
+            ("-(-7)", 7),
+            ("-0", 0),
+            ("-3 < 2", 1),
+            ("2 < -3", 0),
+            ("-3 > -4", 1),
+            ("-128 >= 127", 0),
+            ("-1 <= -1", 1),
+            ("-64 >> 3", -8),
+            ("-1 >> 7", -1),
+            ("-1 >> 127", -1),
+            ("-128 >> 100", -1),
+            ("64 >> 100", 0),
+            ("100 >> 2", 25),
+            ("-6 / 2", -3),
+            ("7 / -2", -3),
+            ("-8 / -2", 4),
+            ("-128 / 1", -128),
+            ("-7 % 2", -1),
+            ("7 % -2", 1),
+            ("-9 % -4", -1),
+            ("9 / 4 + 9 % 4", 3),
+        ];
+        for (expression, expected) in cases {
+            let code = format!("let a = {expression};\nreturn a;");
+            let result = turing_compiler::compile_with_options(&code, &options);
+            assert_eq!(result.run(&[]).unwrap(), expected, "{expression}");
+        }
+
+        let diagnostics = turing_compiler::try_compile_with_options("return 128;", &options)
+            .err()
+            .unwrap();
+        assert_eq!(
+            diagnostics[0].message,
+            "Constant 128 does not fit in 8 bits (-128 to 127)"
+        );
+        assert!(turing_compiler::try_compile("return -1;").is_err());
+
+        let result = turing_compiler::compile("let a = 3;\nreturn -a;");
+        assert_eq!(run_code(result.turing_program), 253);
+        assert_eq!(
+            result.diagnostics[0].message,
+            "Negation of an unsigned number wraps around to 2^8 - x"
+        );
+        let span = result.diagnostics[0].span.unwrap();
+        assert_eq!((span.line, span.column), (2, 8));
+        let result = turing_compiler::compile_with_options("let a = 3;\nreturn -a;", &options);
+        assert!(result.diagnostics.is_empty());
+
+        let result = turing_compiler::compile_with_options("print -3;\nreturn -4;", &options);
+        assert_eq!(result.run_with_output(&[]).unwrap(), (vec![-3], -4));
+    }
+
+    #[test]
+    fn signed_loop() {
+        let code = "
+fn abs(x) {
+    if x < 0 {
+        return -x;
+    };
+    return x;
+};
+fn main() {
+    let i = -3;
+    let sum = 0;
+    while i <= 3 {
+        sum = sum + abs(i);
+        i = i + 1;
+    };
+    return sum - 20;
+};";
+        let options = turing_compiler::CompileOptions {
+            word_bits: 16,
+            signed: true,
+        };
+        let result = turing_compiler::compile_with_options(code, &options);
+        assert_eq!(result.run(&[]).unwrap(), -8);
+    }
+
+    #[test]
+    fn arrays() {
+        let code = "
+let xs = [3; 6];
+let i = 0;
+while i < 6 {
+    xs[i] = xs[i] + i * 2;
+    i = i + 1;
+};
+xs[0] = 40;
+return xs[0] + xs[5] + xs[i - 3];";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 62);
+    }
+
+    #[test]
+    fn arrays_in_recursion() {
+        let code = "
+fn fill(n) {
+    let xs = [n; 3];
+    if n > 0 {
+        let rest = fill(n - 1);
+        xs[1] = rest;
+    };
+    return xs[0] + xs[1] + xs[2];
+};
+fn main() {
+    return fill(3);
+};";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let result = run_code(turing_code);
+        assert_eq!(result, 12);
+    }
+
+    #[test]
+    fn array_errors() {
+        let code = "
+let xs = [0; 4];
+let a = 1;
+xs[4] = 1;
+a[0] = 2;
+return xs;";
+        let diagnostics = turing_compiler::try_compile(code).err().unwrap();
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Index 4 is out of bounds for array 'xs' of size 4",
+                "'a' is not an array",
+                "Array 'xs' can only be used with an index",
+            ]
+        );
+    }
+
+    #[test]
+    fn main_inputs() {
+        let code = "
+fn add(x, y) {
+    return x + y;
+};
+fn main(a, b) {
+    let sum = 0;
+    while a > 0 {
+        sum = add(sum, b);
+        a = a - 1;
+    };
+    return sum + 1;
+};";
+        let result = turing_compiler::compile(code);
+        assert_eq!(result.params.len(), 2);
+        assert_eq!(result.run(&[3, 7]).unwrap(), 22);
+        assert_eq!(result.run(&[10, 2]).unwrap(), 21);
+        assert_eq!(result.run(&[0, 9]).unwrap(), 1);
+        assert_eq!(turing_compiler::compile_and_run(code, &[4, 4]).unwrap(), 17);
+
+        assert_eq!(
+            result.run(&[3]).unwrap_err().message,
+            "main expects 2 inputs, but got 1"
+        );
+        let options = turing_compiler::CompileOptions {
+            word_bits: 4,
+            ..Default::default()
+        };
+        let result = turing_compiler::compile_with_options(code, &options);
+        assert_eq!(
+            result.run(&[3, 16]).unwrap_err().message,
+            "Input 16 does not fit in 4 bits (0 to 15)"
+        );
+    }
+
+    #[test]
+    fn print_primes() {
+        let code = "
+fn is_prime(n) {
+    let d = 2;
+    while d * d <= n {
+        if n % d == 0 {
+            return 0;
+        };
+        d = d + 1;
+    };
+    return 1;
+};
+fn main() {
+    let n = 2;
+    let count = 0;
+    while n < 30 {
+        if is_prime(n) {
+            print n;
+            count = count + 1;
+        };
+        n = n + 1;
+    };
+    return count;
+};";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let (output, result) = run_code_with_output(turing_code);
+        assert_eq!(output, [2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+        assert_eq!(result, 10);
+    }
+
+    #[test]
+    fn print_values() {
+        let code = "
+print 0;
+print 255;
+let a = 7;
+print a * 2;
+return a;";
+        let turing_code = turing_compiler::compile(code).turing_program;
+        let (output, result) = run_code_with_output(turing_code);
+        assert_eq!(output, [0, 255, 14]);
+        assert_eq!(result, 7);
+    }
+
+    #[test]
+    fn cli_args() {
+        let parse = |line: &str| {
+            let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+            crate::parse_args(&args)
+        };
+
+        let args = parse("emit --stage opt-tac - --bits 16 --signed").unwrap();
+        assert_eq!(
+            args.command,
+            crate::Command::Emit(crate::Stage::OptimizedTac)
+        );
+        assert_eq!(args.file, "-");
+        assert_eq!(args.options.word_bits, 16);
+        assert!(args.options.signed);
+
+        let args = parse("build primes.txt -o primes.tm --input 3,7").unwrap();
+        assert_eq!(args.command, crate::Command::Build);
+        assert_eq!(args.output.as_deref(), Some("primes.tm"));
+        assert_eq!(args.inputs, [3, 7]);
+
+        assert_eq!(
+            parse("emit primes.txt").unwrap_err(),
+            "emit needs a --stage"
+        );
+        assert_eq!(
+            parse("emit --stage bytecode primes.txt").unwrap_err(),
+            "Unknown stage 'bytecode'"
+        );
+        assert_eq!(
+            parse("run primes.txt --input 3,x").unwrap_err(),
+            "Input 'x' is not a number from 0 to 255"
+        );
+        assert_eq!(
+            parse("run primes.txt -o out.tm").unwrap_err(),
+            "Unknown option '-o'"
+        );
+        assert_eq!(
+            parse("run").unwrap_err(),
+            "Missing the program file, use - to read it from stdin"
+        );
+    }
+
+    #[test]
+    fn executors() {
+        use turing_vm::{CompiledMachine, Machine, RunOutcome};
+
+        let code = "
+fn f(n) {
+    if n == 0 {
+        return 1;
+    };
+    return n * f(n - 1);
+};
+fn main() {
+    let xs = [0; 4];
+    let i = 0;
+    while i < 4 {
+        xs[i] = i * 3;
+        i = i + 1;
+    };
+    print f(4);
+    return f(5) + xs[3];
+};";
+        let turing_code = turing_compiler::compile(code).turing_program;
+
+        // Single steps, sweeps and the compiled machine end in the same state
+        let mut slow = Machine::load(&turing_code).unwrap();
+        while slow.step().is_none() {}
+        let mut fast = Machine::load(&turing_code).unwrap();
+        assert_eq!(fast.run(), RunOutcome::Halted);
+        let mut compiled = CompiledMachine::new(Machine::load(&turing_code).unwrap());
+        assert_eq!(compiled.run(), RunOutcome::Halted);
+        let compiled = compiled.into_machine();
+        for machine in [&fast, &compiled] {
+            assert_eq!(machine.steps(), slow.steps());
+            assert_eq!((machine.head(), machine.tape()), (slow.head(), slow.tape()));
+        }
+        assert_eq!(run_code(turing_code), 129);
+    }
+
+    #[test]
+    fn watch_variable() {
+        use turing_vm::{Machine, RunOutcome};
+
+        let code = "
+let x = 1;
+let i = 0;
+while i < 3 {
+    x = x * 2;
+    i = i + 1;
+};
+return x;";
+        let result = turing_compiler::compile(code);
+        let location = result
+            .storage
+            .iter()
+            .find(|location| location.name == "global::x");
+        let address = location.unwrap().address;
+
+        // x is set to 1 and doubled in every iteration, the values in between are not seen
+        let watch = [result.watch("global::x").unwrap()];
+        let mut machine = Machine::load(&result.turing_program).unwrap();
+        let assembly = machine.assembly().to_vec();
+        let mut writers = Vec::new();
+        while machine.run_until(&watch, u64::MAX) == Ok(RunOutcome::Breakpoint(0)) {
+            writers.push(assembly[machine.instruction().unwrap() - 1].clone());
+        }
+        assert_eq!(machine.run_until(&watch, u64::MAX), Ok(RunOutcome::Halted));
+        assert_eq!(writers[0], format!("SET S{address} 1"));
+        assert_eq!(writers[1..], vec![format!("STORE S{address} A"); 3]);
+        assert_eq!(result.watch("global::y"), None);
+    }
+
+    #[test]
+    fn run_report() {
+        let code = "
+let x = 5;
+let xs = [1; 3];
+xs[2] = 9;
+print x;
+return x + 1;";
+        let result = turing_compiler::compile(code);
+        let report = result.run_report(&[], u64::MAX).unwrap();
+        assert_eq!(report.outcome, turing_vm::RunOutcome::Halted);
+        assert_eq!(report.a, Some(6));
+        assert_eq!(report.output, [5]);
+        assert_eq!(report.storage["global::x"], Some(5));
+        assert_eq!(report.storage["global::xs[0]"], Some(1));
+        assert_eq!(report.storage["global::xs[2]"], Some(9));
+        assert!(report.steps > 0);
+        assert!(report.tape_extent > 0);
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains("\"outcome\":\"Halted\""));
+
+        let options = turing_compiler::CompileOptions {
+            signed: true,
+            ..Default::default()
+        };
+        let result = turing_compiler::compile_with_options("let y = -3;\nreturn y * 2;", &options);
+        let report = result.run_report(&[], u64::MAX).unwrap();
+        assert_eq!(report.a, Some(-6));
+        assert_eq!(report.storage["global::y"], Some(-3));
+
+        let report = result.run_report(&[], 10).unwrap();
+        assert_eq!(report.outcome, turing_vm::RunOutcome::StepLimit);
+        assert_eq!(report.steps, 10);
+    }
+
+    #[test]
+    fn halted_without_register_a() {
+        let error = try_run_code("!0\nA 0 0 S END").unwrap_err();
+        assert_eq!(
+            error,
+            "The program halted without the register A on the tape"
+        );
+    }
 }
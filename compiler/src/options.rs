@@ -0,0 +1,71 @@
+use crate::diagnostics::Diagnostic;
+
+// Settings for the generated Turing machine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompileOptions {
+    pub word_bits: u32, // number of bits in every storage cell and in A, B and C
+    pub signed: bool,   // numbers are signed in two's complement
+}
+
+impl Default for CompileOptions {
+    fn default() -> CompileOptions {
+        CompileOptions {
+            word_bits: 8,
+            signed: false,
+        }
+    }
+}
+
+impl CompileOptions {
+    // Smallest and largest number that fit in a word
+    pub fn value_range(&self) -> (i128, i128) {
+        if self.signed {
+            let half = 1i128 << (self.word_bits - 1);
+            (-half, half - 1)
+        } else {
+            (0, (1i128 << self.word_bits) - 1)
+        }
+    }
+
+    // Bits of a number as stored in a word, negative numbers are stored in two's complement
+    pub fn encode(&self, value: i128) -> u64 {
+        value as u64 & (u64::MAX >> (64 - self.word_bits))
+    }
+
+    // Number stored in a word, the inverse of encode
+    pub fn decode(&self, bits: u64) -> i128 {
+        let unused_bits = 64 - self.word_bits;
+        if self.signed {
+            // move the sign bit to the first bit of the i64, and shift it back to extend the sign
+            (((bits << unused_bits) as i64) >> unused_bits) as i128
+        } else {
+            bits as i128
+        }
+    }
+
+    // Bits of the inputs of the program, there is one input for every parameter of main
+    pub fn encode_inputs(&self, param_count: usize, inputs: &[u8]) -> Result<Vec<u64>, Diagnostic> {
+        if inputs.len() != param_count {
+            return Err(Diagnostic::error(format!(
+                "main expects {} inputs, but got {}",
+                param_count,
+                inputs.len()
+            )));
+        }
+
+        let (min, max) = self.value_range();
+        inputs
+            .iter()
+            .map(|&input| {
+                if input as i128 > max {
+                    Err(Diagnostic::error(format!(
+                        "Input {input} does not fit in {} bits ({min} to {max})",
+                        self.word_bits
+                    )))
+                } else {
+                    Ok(self.encode(input as i128))
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,88 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::Serialize;
+use turing_vm::{output, Machine, RunOutcome};
+
+use crate::a5code_generator::StorageLocation;
+use crate::options::CompileOptions;
+use crate::storage_cells;
+use crate::symbols::{symtou8, TapeSymbols};
+
+// The state of a program after running it, for the debugger frontends.
+// Values that are not only 0 and 1 bits, because the machine stopped in the middle of an operation, are None.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    pub outcome: RunOutcome,
+    pub a: Option<i128>,
+    pub b: Option<i128>,
+    pub c: Option<i128>,
+    pub storage: BTreeMap<String, Option<i128>>, // by variable name, array elements are name[index]
+    pub output: Vec<i128>,
+    pub steps: u64,
+    pub tape_extent: usize, // number of cells the tape grew to
+    pub wall_time_ms: f64,
+}
+
+impl RunReport {
+    pub fn new(
+        machine: &Machine,
+        outcome: RunOutcome,
+        wall_time: Duration,
+        locations: &[StorageLocation],
+        options: &CompileOptions,
+    ) -> RunReport {
+        let tape = machine.tape().to_vec();
+        let symbol = |symbol| tape.iter().position(|&x| x == symtou8(symbol));
+        let decode = |bits: &[u8]| {
+            let value = bits.iter().try_fold(0u64, |value, &bit| match bit {
+                0 | 1 => Some(value << 1 | bit as u64),
+                _ => None,
+            })?;
+            Some(options.decode(value))
+        };
+
+        // the working area is Middle StartA [A] ABsep [B] EndB [C] EndC
+        let register = |start: Option<usize>, end: Option<usize>| match (start, end) {
+            (Some(start), Some(end)) if start < end => decode(&tape[start + 1..end]),
+            _ => None,
+        };
+        let start_a = symbol(TapeSymbols::StartA);
+        let end_a = symbol(TapeSymbols::EndA);
+        let end_b = symbol(TapeSymbols::EndB);
+        let end_c = symbol(TapeSymbols::EndC);
+
+        let mut storage = BTreeMap::new();
+        if let Some(middle) = symbol(TapeSymbols::Middle) {
+            let cell = |address: usize| {
+                let (start, end) = storage_cells(middle as i64, address, options.word_bits);
+                decode(tape.get(usize::try_from(start).ok()?..end as usize)?)
+            };
+
+            for location in locations {
+                if location.size == 1 {
+                    storage.insert(location.name.clone(), cell(location.address));
+                } else {
+                    for index in 0..location.size {
+                        let name = format!("{}[{}]", location.name, index);
+                        storage.insert(name, cell(location.address + index));
+                    }
+                }
+            }
+        }
+
+        RunReport {
+            outcome,
+            a: register(start_a, end_a),
+            b: register(end_a, end_b),
+            c: register(end_b, end_c),
+            storage,
+            output: output(&tape)
+                .into_iter()
+                .map(|value| options.decode(value))
+                .collect(),
+            steps: machine.steps(),
+            tape_extent: tape.len(),
+            wall_time_ms: wall_time.as_secs_f64() * 1000.0,
+        }
+    }
+}
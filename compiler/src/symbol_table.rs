@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use crate::diagnostics::{Diagnostic, Span};
+
+// Scope of variables, a function body or a block inside of it
+#[derive(Debug, Default)]
+struct Scope {
+    vars: HashMap<String, String>, // <source name, storage name>
+}
+
+// Keeps track of which variables are visible where, and gives every declaration a unique storage name.
+// Storage names are "function::name", top level code uses "global" as function name.
+// When a name is declared again in the same function, a counter is added, e.g. "main::a#2".
+// The storage name of every declaration and use is kept by the start of the name in the source code,
+// so later stages use the same names without repeating the scoping rules.
+#[derive(Debug)]
+pub struct SymbolTable {
+    scopes: Vec<Scope>,
+    function: String,
+    declarations: HashMap<String, u32>, // <storage name, number of declarations>
+    pub names: HashMap<usize, String>,  // <start of the name in the source code, storage name>
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable {
+            scopes: vec![Scope::default()],
+            function: "global".to_string(),
+            declarations: HashMap::new(),
+            names: HashMap::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    // Functions can see the global variables declared before them
+    pub fn enter_function(&mut self, name: &str) {
+        self.function = name.to_string();
+        self.enter_block();
+    }
+
+    pub fn exit_function(&mut self) {
+        self.exit_block();
+        self.function = "global".to_string();
+    }
+
+    pub fn enter_block(&mut self) {
+        self.scopes.push(Scope::default());
+    }
+
+    pub fn exit_block(&mut self) {
+        self.scopes.pop();
+    }
+
+    // Declare a new variable in the innermost scope, and return its storage name
+    pub fn declare(&mut self, name: &str, span: Span) -> String {
+        if self.lookup(name).is_some() {
+            self.diagnostics.push(
+                Diagnostic::warning(format!(
+                    "Declaration of '{name}' shadows an earlier declaration"
+                ))
+                .with_span(span),
+            );
+        }
+
+        let base = format!("{}::{name}", self.function);
+        let count = self.declarations.entry(base.clone()).or_insert(0);
+        *count += 1;
+        let storage_name = match *count {
+            1 => base,
+            n => format!("{base}#{n}"),
+        };
+
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .vars
+            .insert(name.to_string(), storage_name.clone());
+        self.names.insert(span.start, storage_name.clone());
+
+        storage_name
+    }
+
+    // Find the storage name of a variable, reports an error when it is not declared
+    pub fn resolve(&mut self, name: &str, span: Span) -> String {
+        match self.lookup(name) {
+            Some(storage_name) => {
+                self.names.insert(span.start, storage_name.clone());
+                storage_name
+            }
+            None => {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("Use of undeclared variable '{name}'"))
+                        .with_span(span),
+                );
+                name.to_string()
+            }
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.vars.get(name).cloned())
+    }
+}
@@ -1,196 +1,75 @@
-use std::{collections::HashMap, fs, time::Instant};
+use std::{
+    env, fs, process,
+    time::{Duration, Instant},
+};
 
-#[derive(Debug, Default, Clone)]
-enum Action {
-    #[default]
-    Stay,
-    Left,
-    Right,
-}
-
-#[derive(Debug, Default, Clone)]
-struct Instruction {
-    write_symbol: u32,
-    action: Action,
-    next_state: u32,
-}
-
-#[derive(Debug)]
-struct TuringMachine {
-    current_state: u32,
-    current_position: usize,
-    tape: Vec<u32>,
-    instructions: Vec<Instruction>,
-    step: u64,
-    end_state: u32,
-}
-
-impl TuringMachine {
-    fn from_file(filename: &str) -> Self {
-        let contents = fs::read_to_string(filename).unwrap();
-        let mut lines = contents.lines().peekable();
-        let mut instructions = Vec::new();
-
-        let mut state_mapping = HashMap::new();
-        let mut state_counter: u32 = 0;
-
-        let tape_start = lines
-            .peek()
-            .unwrap()
-            .split_whitespace()
-            .enumerate()
-            .find(|(_, s)| s.starts_with("!"))
-            .map(|(i, _)| i)
-            .unwrap_or(0);
-
-        let initial_tape = lines
-            .next()
-            .unwrap()
-            .split_whitespace()
-            .map(|s| s.replace("!", "").parse::<u32>().unwrap())
-            .collect();
-
-        for line in lines {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() < 5 {
-                continue;
-            } else if line.starts_with("#") {
-                continue;
-            }
-            let current_state = parts[0].to_string();
-
-            let current_state_num =
-                *state_mapping
-                    .entry(current_state.clone())
-                    .or_insert_with(|| {
-                        let current = state_counter;
-                        state_counter += 1;
-                        current
-                    });
-
-            let next_state = parts[4].to_string();
-
-            let next_state_num = *state_mapping.entry(next_state.clone()).or_insert_with(|| {
-                let current = state_counter;
-                state_counter += 1;
-                current
-            });
-
-            let read_symbol;
-            if parts[1].parse::<u32>().is_ok() {
-                read_symbol = parts[1].parse::<u32>().unwrap();
-            } else if parts[1] == "_" {
-                read_symbol = 255;
-            } else {
-                println!("{:?}", parts);
-                panic!("Invalid read symbol");
-            }
-
-            let write_symbol;
-            if parts[2].parse::<u32>().is_ok() {
-                write_symbol = parts[2].parse::<u32>().unwrap();
-            } else if parts[2] == "_" {
-                write_symbol = 255;
-            } else {
-                println!("{:?}", parts);
-                panic!("Invalid write symbol");
-            }
+use turing_vm::{output, register_a, to_number, CompiledMachine, LoadOptions, Machine, RunOutcome};
 
-            let action = match parts[3] {
-                "S" => Action::Stay,
-                "L" => Action::Left,
-                "R" => Action::Right,
-                _ => panic!("Invalid action"),
-            };
-
-            let instruction = Instruction {
-                write_symbol,
-                action,
-                next_state: next_state_num,
-            };
-
-            instructions.resize(
-                ((state_counter << 8) | 255 + 1) as usize,
-                Default::default(),
-            );
-
-            //instructions.insert((current_state_num, read_symbol), instruction);
-            instructions[((current_state_num << 8) | read_symbol) as usize] = instruction;
-        }
-
-        Self {
-            current_state: 0,
-            current_position: tape_start,
-            tape: initial_tape,
-            instructions,
-            step: 0,
-            end_state: state_counter,
+// Usage: interpreter [file] [--strict] [--compiled] [--max-steps <n>] [--timeout <seconds>], the file is input.txt by default.
+// --compiled runs the program with the compiled transition table.
+fn main() {
+    let mut file = "input.txt".to_string();
+    let mut options = LoadOptions::default();
+    let mut max_steps = u64::MAX;
+    let mut timeout = None;
+    let mut compiled = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strict" => options.strict = true,
+            "--compiled" => compiled = true,
+            "--max-steps" => max_steps = parse_number(&arg, args.next()),
+            "--timeout" => timeout = Some(Duration::from_secs(parse_number(&arg, args.next()))),
+            _ => file = arg,
         }
     }
 
-    fn print_current_step(&self) {
-        let mut tape_str = String::new();
-        for (i, symbol) in self.tape.iter().enumerate() {
-            if i == self.current_position {
-                tape_str.push_str(&format!("[{}]", symbol));
-            } else {
-                tape_str.push_str(&format!(" {} ", symbol));
-            }
+    let code = fs::read_to_string(&file).unwrap();
+    let mut machine = match Machine::load_with_options(&code, &options) {
+        Ok(machine) => machine,
+        Err(error) => {
+            eprintln!("{file}: {error}");
+            process::exit(1);
         }
-        let state_str = format!("state: {}", self.current_state);
-        let padding = " ".repeat(40 - state_str.len());
-        println!("{}{}tape: {}", state_str, padding, tape_str);
+    };
+    for warning in machine.warnings() {
+        eprintln!("{file}: warning: {warning}");
     }
 
-    fn step(&mut self) {
-        //self.print_current_step();
-
-        // let instruction = self
-        //     .instructions
-        //     .get(&(self.current_state, self.tape[self.current_position]))
-        //     .unwrap();
-
-        let instruction = &self.instructions
-            [((self.current_state << 8) | self.tape[self.current_position]) as usize];
-
-        self.tape[self.current_position] = instruction.write_symbol;
-        match instruction.action {
-            Action::Stay => (),
-            Action::Left => {
-                if self.current_position == 0 {
-                    self.tape.insert(0, 255);
-                } else {
-                    self.current_position -= 1;
-                }
-            }
-            Action::Right => {
-                self.current_position += 1;
-                if self.current_position == self.tape.len() {
-                    self.tape.push(255);
-                }
-            }
-        }
-        self.current_state = instruction.next_state;
-        self.step += 1;
+    let start = Instant::now();
+    let deadline = timeout.map(|timeout| start + timeout);
+    let outcome = if compiled {
+        let mut executor = CompiledMachine::new(machine);
+        let outcome = executor.run_with_limit(max_steps, deadline);
+        machine = executor.into_machine();
+        outcome
+    } else {
+        machine.run_with_limit(max_steps, deadline)
+    };
+    println!("Program {outcome}");
+    println!("Program took {:?}", start.elapsed());
+
+    let tape = machine.tape().to_vec();
+    println!("Final tape: {:?}", tape);
+    println!("Total steps: {}", machine.steps());
+
+    println!("Output: {:?}", output(&tape));
+    if let Some(a) = register_a(&tape) {
+        println!("Result: {}", to_number(a));
     }
 
-    fn run(&mut self) {
-        let start = Instant::now();
-        loop {
-            if self.current_state == self.end_state - 1 {
-                println!("End state reached. Halting.");
-                break;
-            }
-            self.step();
-        }
-        let duration = start.elapsed();
-        println!("Program took {:?}", duration);
+    if outcome != RunOutcome::Halted {
+        process::exit(1);
     }
 }
 
-fn main() {
-    let mut turing_machine = TuringMachine::from_file("input.txt");
-    turing_machine.run();
-    println!("Final tape: {:?}", turing_machine.tape);
-    println!("Total steps: {}", turing_machine.step);
+fn parse_number(option: &str, value: Option<String>) -> u64 {
+    match value.and_then(|value| value.parse().ok()) {
+        Some(number) => number,
+        None => {
+            eprintln!("{option} expects a number");
+            process::exit(2);
+        }
+    }
 }
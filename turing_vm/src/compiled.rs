@@ -0,0 +1,286 @@
+use std::time::Instant;
+
+use crate::{
+    machine::{Action, Machine, RunOutcome, DEADLINE_INTERVAL},
+    tape::{Tape, BLANK},
+};
+
+// A transition with the move of the head baked in, next is NO_TRANSITION when the machine stops.
+// sweep is set when the transition keeps the state and the symbol, so a run of such cells is passed in one loop.
+#[derive(Debug, Clone, Copy)]
+struct Transition {
+    next: u32,
+    write: u8,
+    delta: i8,
+    sweep: bool,
+}
+
+const NO_TRANSITION: u32 = u32::MAX;
+
+const STOP: Transition = Transition {
+    next: NO_TRANSITION,
+    write: 0,
+    delta: 0,
+    sweep: false,
+};
+
+// A machine with its transition table compiled into a jump table per state, for long runs.
+// The symbols that appear in the program are numbered, so a row only has an entry for each of them and the table stays small.
+// A step is one lookup in the table, the halting state and missing transitions stop the run with the same lookup.
+// During a run the tape is kept in one buffer, which doubles in size when the head moves past either end.
+#[derive(Debug, Clone)]
+pub struct CompiledMachine {
+    machine: Machine,
+    table: Vec<Transition>, // indexed by (state << shift) | columns[symbol]
+    columns: [u16; 256],
+    shift: u32,
+}
+
+impl CompiledMachine {
+    pub fn new(machine: Machine) -> Self {
+        let halt_state = machine.state_names.len() - 1;
+
+        // Column 0 is for the symbols that no transition reads or writes, and the symbols on the tape
+        let mut columns = [0; 256];
+        let mut used = 1;
+        for (index, instruction) in machine.instructions.iter().enumerate() {
+            if let Some(instruction) = instruction {
+                for symbol in [index & 0xff, instruction.write_symbol as usize] {
+                    if columns[symbol] == 0 {
+                        columns[symbol] = used;
+                        used += 1;
+                    }
+                }
+            }
+        }
+        let shift = (used as u32).next_power_of_two().trailing_zeros();
+
+        let mut table = vec![STOP; machine.state_names.len() << shift];
+        for (index, instruction) in machine.instructions.iter().enumerate() {
+            let state = index >> 8;
+            if let (Some(instruction), false) = (instruction, state == halt_state) {
+                let column = columns[index & 0xff] as usize;
+                let delta = match instruction.action {
+                    Action::Stay => 0,
+                    Action::Left => -1,
+                    Action::Right => 1,
+                };
+                table[(state << shift) | column] = Transition {
+                    next: instruction.next_state,
+                    write: instruction.write_symbol,
+                    delta,
+                    sweep: instruction.next_state as usize == state
+                        && instruction.write_symbol as usize == index & 0xff
+                        && delta != 0,
+                };
+            }
+        }
+
+        Self {
+            machine,
+            table,
+            columns,
+            shift,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn into_machine(self) -> Machine {
+        self.machine
+    }
+
+    // Run until the machine halts or gets stuck
+    pub fn run(&mut self) -> RunOutcome {
+        self.run_with_limit(u64::MAX, None)
+    }
+
+    // Run like Machine::run_with_limit, the machine ends in the same state with the same tape and step count.
+    // The steps are not recorded, so the history of the machine is cleared.
+    // The deadline is only checked every few thousand steps.
+    pub fn run_with_limit(&mut self, max_steps: u64, deadline: Option<Instant>) -> RunOutcome {
+        if self.machine.is_halted() {
+            return RunOutcome::Halted;
+        }
+
+        let halt_state = self.machine.state_names.len() - 1;
+        let mut buffer = Buffer::new(&self.machine.tape);
+        let mut head = (self.machine.head - buffer.offset) as usize;
+        let mut state = self.machine.state as usize;
+        let mut steps = 0;
+
+        let outcome = 'run: loop {
+            let chunk = (max_steps - steps).min(DEADLINE_INTERVAL);
+            if chunk == 0 {
+                break RunOutcome::StepLimit;
+            }
+
+            let end = steps + chunk;
+            while steps < end {
+                let symbol = buffer.cells[head];
+                let row = state << self.shift;
+                let transition = self.table[row | self.columns[symbol as usize] as usize];
+                if transition.next == NO_TRANSITION {
+                    break 'run if state == halt_state {
+                        RunOutcome::Halted
+                    } else {
+                        RunOutcome::NoTransition {
+                            state: self.machine.state_names[state].clone(),
+                            symbol,
+                            head: buffer.offset + head as i64,
+                        }
+                    };
+                }
+
+                if transition.sweep {
+                    // Pass the cells with the same transition, the cells and the state do not change
+                    let delta = transition.delta as isize;
+                    while steps < end {
+                        head = head.wrapping_add_signed(delta);
+                        steps += 1;
+                        if head >= buffer.cells.len() {
+                            head = buffer.grow(head);
+                        }
+                        let next =
+                            self.table[row | self.columns[buffer.cells[head] as usize] as usize];
+                        if !next.sweep || next.delta != transition.delta {
+                            break;
+                        }
+                    }
+                } else {
+                    buffer.cells[head] = transition.write;
+                    head = head.wrapping_add_signed(transition.delta as isize);
+                    state = transition.next as usize;
+                    steps += 1;
+                    if head >= buffer.cells.len() {
+                        head = buffer.grow(head);
+                    }
+                }
+                buffer.visit(head);
+            }
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break RunOutcome::Timeout;
+            }
+        };
+
+        self.machine.state = state as u32;
+        self.machine.head = buffer.offset + head as i64;
+        self.machine.tape = buffer.into_tape();
+        self.machine.steps += steps;
+        if let Some(history) = &mut self.machine.history {
+            history.clear();
+        }
+
+        if outcome == RunOutcome::StepLimit && self.machine.is_halted() {
+            RunOutcome::Halted
+        } else {
+            outcome
+        }
+    }
+}
+
+// The tape in one vector with blanks on both sides, cells[i] is the cell with coordinate offset + i.
+// first and last are the outermost cells of the tape or that the head has been on.
+struct Buffer {
+    cells: Vec<u8>,
+    offset: i64,
+    first: usize,
+    last: usize,
+}
+
+impl Buffer {
+    fn new(tape: &Tape) -> Buffer {
+        let margin = tape.len().max(64);
+        let mut cells = vec![BLANK; margin];
+        cells.extend(tape.iter());
+        cells.resize(cells.len() + margin, BLANK);
+
+        Buffer {
+            cells,
+            offset: tape.start() - margin as i64,
+            first: margin,
+            last: margin + tape.len() - 1,
+        }
+    }
+
+    // Double the buffer on the side the head moved out of, returns the new index of the head
+    fn grow(&mut self, head: usize) -> usize {
+        let size = self.cells.len();
+        if head == size {
+            self.cells.resize(size * 2, BLANK);
+            head
+        } else {
+            let mut cells = vec![BLANK; size];
+            cells.append(&mut self.cells);
+            self.cells = cells;
+            self.offset -= size as i64;
+            self.first += size;
+            self.last += size;
+            head.wrapping_add(size)
+        }
+    }
+
+    fn visit(&mut self, head: usize) {
+        if head < self.first {
+            self.first = head;
+        } else if head > self.last {
+            self.last = head;
+        }
+    }
+
+    fn into_tape(self) -> Tape {
+        Tape::from_cells(
+            &self.cells[self.first..=self.last],
+            self.offset + self.first as i64,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CompiledMachine, Machine, RunOutcome};
+
+    // Runs the program with both executors and checks that they end the same way
+    fn compare(program: &str, max_steps: u64) -> RunOutcome {
+        let mut machine = Machine::load(program).unwrap();
+        let outcome = machine.run_with_limit(max_steps, None);
+        let mut compiled = CompiledMachine::new(Machine::load(program).unwrap());
+        assert_eq!(compiled.run_with_limit(max_steps, None), outcome);
+
+        let compiled = compiled.into_machine();
+        assert_eq!(compiled.steps(), machine.steps());
+        assert_eq!(compiled.state_name(), machine.state_name());
+        assert_eq!(
+            (compiled.head(), compiled.tape()),
+            (machine.head(), machine.tape())
+        );
+        outcome
+    }
+
+    #[test]
+    fn compiled_machine() {
+        let left = "3 !4\nA 4 4 L B\nB 3 3 L C\nC _ 1 L D\nD _ 2 R E\nE 1 1 R F\nF 3 3 S END";
+        assert_eq!(compare(left, u64::MAX), RunOutcome::Halted);
+        // Sweeps to the right and back, and a step limit in the middle of a sweep
+        let sweeps =
+            "!0 0 0 1 1 2\nA 0 0 R A\nA 1 1 R A\nA 2 2 L B\nB 0 0 L B\nB 1 1 L B\nB _ 3 S END";
+        assert_eq!(compare(sweeps, u64::MAX), RunOutcome::Halted);
+        assert_eq!(compare(sweeps, 7), RunOutcome::StepLimit);
+
+        // The buffer grows on both sides
+        for walk in [
+            "!0\nA 0 0 L A\nA _ 1 L A\nA 2 2 S END",
+            "!0\nA 0 0 R A\nA _ 1 R A\nA 2 2 S END",
+        ] {
+            assert_eq!(compare(walk, 1000), RunOutcome::StepLimit);
+        }
+        let stuck = "!0 1\nA 0 0 R A\nA 2 2 S END";
+        assert!(matches!(
+            compare(stuck, u64::MAX),
+            RunOutcome::NoTransition { head: 1, .. }
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::machine::{Machine, RunOutcome};
+
+// Where run_until stops
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breakpoint {
+    // The start of the assembly instruction with this index in Machine::assembly
+    Instruction(usize),
+    // The start of the code after a label, the instruction `label:`
+    Label(String),
+    // Entering the Turing state with this name
+    State(String),
+    // A new number in the bits from coordinate start up to end on the tape
+    Watch { start: i64, end: i64 },
+}
+
+// A breakpoint with the names resolved to numbers and the cells of a watch on the tape
+enum Check {
+    Instruction(usize),
+    State(u32),
+    Watch {
+        cells: (i64, i64), // the coordinates of the first cell and after the last cell
+        value: Option<u64>,
+        written: bool, // the cells were written since the last check
+    },
+}
+
+impl Machine {
+    // The assembly instructions from the #asm headers of the program
+    pub fn assembly(&self) -> &[String] {
+        &self.assembly
+    }
+
+    // The index in assembly of the instruction the current state belongs to
+    pub fn instruction(&self) -> Option<usize> {
+        self.state_instructions[self.state as usize]
+    }
+
+    // Run until a breakpoint is reached, the machine halts or gets stuck, for at most max_steps steps.
+    // Breakpoints are checked after every step, so the machine does at least one step.
+    // A watch stops when the cell holds another number at the end of an assembly instruction,
+    // so the values an instruction leaves in the cell while it writes it are skipped.
+    pub fn run_until(
+        &mut self,
+        breakpoints: &[Breakpoint],
+        max_steps: u64,
+    ) -> Result<RunOutcome, String> {
+        let mut checks = breakpoints
+            .iter()
+            .map(|breakpoint| self.check(breakpoint))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut steps = 0;
+        while steps < max_steps {
+            // A sweep keeps the state and the cells, so it can not reach a breakpoint
+            steps += self.sweep(max_steps - steps);
+            if steps == max_steps {
+                break;
+            }
+
+            let (state, head) = (self.state, self.head);
+            if let Some(outcome) = self.step() {
+                return Ok(outcome);
+            }
+            steps += 1;
+            let ended = self.assembly.is_empty()
+                || self.instruction() != self.state_instructions[state as usize];
+
+            for (index, check) in checks.iter_mut().enumerate() {
+                let reached = match check {
+                    Check::Instruction(instruction) => {
+                        let current = Some(*instruction);
+                        self.instruction() == current
+                            && self.state_instructions[state as usize] != current
+                    }
+                    Check::State(target) => self.state == *target && state != *target,
+                    Check::Watch {
+                        cells,
+                        value,
+                        written,
+                    } => {
+                        *written |= (cells.0..cells.1).contains(&head);
+                        if *written && ended {
+                            *written = false;
+                            let number = self.number(*cells);
+                            let changed = value.is_some() && number.is_some() && number != *value;
+                            *value = number.or(*value);
+                            changed
+                        } else {
+                            false
+                        }
+                    }
+                };
+                if reached {
+                    return Ok(RunOutcome::Breakpoint(index));
+                }
+            }
+        }
+
+        Ok(if self.is_halted() {
+            RunOutcome::Halted
+        } else {
+            RunOutcome::StepLimit
+        })
+    }
+
+    fn check(&self, breakpoint: &Breakpoint) -> Result<Check, String> {
+        match breakpoint {
+            Breakpoint::Instruction(index) if *index < self.assembly.len() => {
+                Ok(Check::Instruction(self.after_labels(*index)))
+            }
+            Breakpoint::Instruction(index) => Err(format!(
+                "Instruction {index} is not in the program, it has {} instructions",
+                self.assembly.len()
+            )),
+            Breakpoint::Label(label) => self
+                .assembly
+                .iter()
+                .position(|instruction| *instruction == format!("{label}:"))
+                .map(|index| Check::Instruction(self.after_labels(index)))
+                .ok_or_else(|| format!("Label {label} is not in the program")),
+            Breakpoint::State(name) => self
+                .state_names
+                .iter()
+                .position(|state| state == name)
+                .map(|state| Check::State(state as u32))
+                .ok_or_else(|| format!("State {name} is not in the program")),
+            Breakpoint::Watch { start, end } => {
+                let cells = (*start, *end);
+                Ok(Check::Watch {
+                    cells,
+                    value: self.number(cells),
+                    written: false,
+                })
+            }
+        }
+    }
+
+    // The code before a label runs into the instruction after it without passing the state of the label,
+    // so a breakpoint on a label is moved to the first instruction after it that is not a label
+    fn after_labels(&self, index: usize) -> usize {
+        (index..self.assembly.len())
+            .find(|&index| !self.assembly[index].ends_with(':'))
+            .unwrap_or(index)
+    }
+
+    // The number in the cells, when they only hold bits
+    fn number(&self, (start, end): (i64, i64)) -> Option<u64> {
+        (start..end).try_fold(0, |value, position| match self.tape.get(position) {
+            bit @ (0 | 1) => Some(value << 1 | bit as u64),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Breakpoint, Machine, RunOutcome};
+
+    // Set the two bits to 1, go back to the start and clear them again
+    const PROGRAM: &str = "!0 0
+#asm SET
+FILL 0 1 R FILL
+FILL _ _ L BACK
+#asm L1:
+#asm BACK
+BACK 1 1 L BACK
+BACK _ _ R CLEAR
+#asm CLEAR
+CLEAR 1 0 R CLEAR
+CLEAR _ _ S END";
+
+    #[test]
+    fn breakpoints() {
+        let mut machine = Machine::load(PROGRAM).unwrap();
+        assert_eq!(machine.assembly(), ["SET", "L1:", "BACK", "CLEAR"]);
+        assert_eq!(machine.instruction(), Some(0));
+
+        // A label stops at the first instruction after it
+        let breakpoints = [
+            Breakpoint::Label("L1".to_string()),
+            Breakpoint::State("CLEAR".to_string()),
+        ];
+        assert_eq!(
+            machine.run_until(&breakpoints, u64::MAX),
+            Ok(RunOutcome::Breakpoint(0))
+        );
+        assert_eq!((machine.instruction(), machine.steps()), (Some(2), 3));
+        assert_eq!(
+            machine.run_until(&breakpoints, u64::MAX),
+            Ok(RunOutcome::Breakpoint(1))
+        );
+        assert_eq!(machine.state_name(), "CLEAR");
+        assert_eq!(
+            machine.run_until(&breakpoints, u64::MAX),
+            Ok(RunOutcome::Halted)
+        );
+
+        let mut machine = Machine::load(PROGRAM).unwrap();
+        let breakpoints = [Breakpoint::Instruction(3)];
+        assert_eq!(
+            machine.run_until(&breakpoints, 2),
+            Ok(RunOutcome::StepLimit)
+        );
+        assert_eq!(
+            machine.run_until(&breakpoints, u64::MAX),
+            Ok(RunOutcome::Breakpoint(0))
+        );
+        assert_eq!(machine.instruction(), Some(3));
+
+        let error = machine.run_until(&[Breakpoint::Label("L99".to_string())], 10);
+        assert_eq!(error, Err("Label L99 is not in the program".to_string()));
+        let error = machine.run_until(&[Breakpoint::Instruction(4)], 10);
+        assert_eq!(
+            error,
+            Err("Instruction 4 is not in the program, it has 4 instructions".to_string())
+        );
+    }
+
+    #[test]
+    fn watch() {
+        // The bits are read when an instruction ends, so the values in between are not seen
+        let mut machine = Machine::load(PROGRAM).unwrap();
+        let watch = [Breakpoint::Watch { start: 0, end: 2 }];
+        assert_eq!(
+            machine.run_until(&watch, u64::MAX),
+            Ok(RunOutcome::Breakpoint(0))
+        );
+        assert_eq!(
+            (machine.state_name(), machine.tape().to_vec()),
+            ("BACK", vec![1, 1, 255])
+        );
+        assert_eq!(
+            machine.run_until(&watch, u64::MAX),
+            Ok(RunOutcome::Breakpoint(0))
+        );
+        assert!(machine.is_halted());
+        assert_eq!((machine.tape().get(0), machine.tape().get(1)), (0, 0));
+        assert_eq!(machine.run_until(&watch, u64::MAX), Ok(RunOutcome::Halted));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{machine::Machine, tape::Tape};
+
+// What a step changed: the cell under the head with the symbol before the step, and the state before the step.
+// grew is set when the step added a cell to the tape.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Change {
+    pub head: i64,
+    pub state: u32,
+    pub symbol: u8,
+    pub grew: bool,
+}
+
+// A copy of everything that changes while the machine runs, to restore it later
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    state: u32,
+    head: i64,
+    tape: Tape,
+    steps: u64,
+}
+
+impl Snapshot {
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+
+impl Machine {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            state: self.state,
+            head: self.head,
+            tape: self.tape.clone(),
+            steps: self.steps,
+        }
+    }
+
+    // Go back to a snapshot of this program, the recorded history is cleared
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.state = snapshot.state;
+        self.head = snapshot.head;
+        self.tape = snapshot.tape.clone();
+        self.steps = snapshot.steps;
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+    }
+
+    // Start or stop recording the changes of every step, the changes recorded so far are dropped
+    pub fn record_history(&mut self, record: bool) {
+        self.history = record.then(Vec::new);
+    }
+
+    // The number of steps that can be undone
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, Vec::len)
+    }
+
+    // Undo the last recorded step, returns false when there is nothing to undo
+    pub fn step_back(&mut self) -> bool {
+        let Some(change) = self.history.as_mut().and_then(Vec::pop) else {
+            return false;
+        };
+
+        if change.grew {
+            self.tape.remove_edge(self.head);
+        }
+        self.tape.set(change.head, change.symbol);
+        self.head = change.head;
+        self.state = change.state;
+        self.steps -= 1;
+        true
+    }
+
+    // Step forwards or backwards to the step number, returns false when the machine stopped before it.
+    // Going back is limited to the recorded history.
+    pub fn seek(&mut self, step: u64) -> bool {
+        while self.steps > step {
+            if !self.step_back() {
+                return false;
+            }
+        }
+        if self.steps < step {
+            self.run_with_limit(step - self.steps, None);
+        }
+        self.steps == step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Machine, RunOutcome};
+
+    #[test]
+    fn history() {
+        // Fill the 0s with 1s, write a 2 past the right end and a 3 past the left end
+        let program = "!0 0 0\nA 0 1 R A\nA _ 2 L B\nB 1 1 L B\nB _ 3 S END";
+        let mut machine = Machine::load(program).unwrap();
+        let start = machine.snapshot();
+        machine.record_history(true);
+
+        assert!(machine.seek(4));
+        let middle = machine.snapshot();
+        assert_eq!(machine.run(), RunOutcome::Halted);
+        let end = machine.snapshot();
+        assert_eq!(end.steps(), 8);
+        assert_eq!(machine.history_len() as u64, end.steps());
+
+        // Every step can be undone, also the ones that grew the tape
+        assert!(machine.seek(4));
+        assert_eq!(machine.snapshot(), middle);
+        while machine.step_back() {}
+        assert_eq!(machine.snapshot(), start);
+        assert!(!machine.seek(end.steps() + 1));
+        assert_eq!(machine.snapshot(), end);
+
+        // Restoring clears the history, so it can not go back before the snapshot
+        machine.restore(&middle);
+        assert_eq!(machine.history_len(), 0);
+        assert!(!machine.seek(2));
+        assert_eq!(machine.steps(), 4);
+        machine.run();
+        assert_eq!(machine.snapshot(), end);
+    }
+}
@@ -0,0 +1,15 @@
+mod compiled;
+mod debug;
+mod history;
+mod load;
+mod machine;
+mod registers;
+mod tape;
+
+pub use compiled::CompiledMachine;
+pub use debug::Breakpoint;
+pub use history::Snapshot;
+pub use load::{LoadError, LoadOptions};
+pub use machine::{Action, Machine, RunOutcome};
+pub use registers::{output, register_a, to_number};
+pub use tape::{Tape, BLANK};
@@ -0,0 +1,239 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    machine::{Action, Instruction},
+    tape::{Tape, BLANK},
+};
+
+// A problem in a line of a Turing program, lines are numbered from 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LoadOptions {
+    // Reject lines that are not a transition, and two different transitions for the same state and symbol.
+    // Otherwise such lines are skipped, and the last transition is used, with a warning.
+    pub strict: bool,
+}
+
+// The parts of a Machine read from a Turing program
+pub(crate) struct Program {
+    pub tape: Tape,
+    pub head: i64,
+    pub instructions: Vec<Option<Instruction>>, // indexed by (state << 8) | symbol
+    pub state_names: Vec<String>,
+    pub assembly: Vec<String>,
+    pub state_instructions: Vec<Option<usize>>, // the assembly instruction of every state
+    pub warnings: Vec<LoadError>,
+}
+
+pub(crate) fn parse(code: &str, options: &LoadOptions) -> Result<Program, LoadError> {
+    let mut lines = code.lines().enumerate().map(|(i, line)| (i + 1, line));
+
+    let (_, tape_line) = lines.next().ok_or_else(|| error(1, "Missing the tape"))?;
+    let (tape, head) = parse_tape(tape_line)?;
+
+    let mut instructions: Vec<Option<Instruction>> = Vec::new();
+    let mut state_mapping = HashMap::new();
+    let mut state_names = Vec::new();
+    let mut defined_on = HashMap::new(); // <(state, symbol), line of the transition>
+    let mut warnings = Vec::new();
+    // The compiler writes `#asm <instruction>` before the states of every assembly instruction
+    let mut assembly = Vec::new();
+    let mut state_instructions = Vec::new();
+
+    let mut state_number = |name: &str| -> u32 {
+        *state_mapping.entry(name.to_string()).or_insert_with(|| {
+            state_names.push(name.to_string());
+            state_names.len() as u32 - 1
+        })
+    };
+
+    for (line_number, line) in lines {
+        if let Some(instruction) = line.strip_prefix("#asm ") {
+            assembly.push(instruction.trim().to_string());
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.is_empty() || line.starts_with("#") {
+            continue;
+        }
+        if parts.len() != 5 {
+            let problem = error(
+                line_number,
+                format!(
+                    "Expected a transition with 5 fields, but got {} fields",
+                    parts.len()
+                ),
+            );
+            if options.strict {
+                return Err(problem);
+            }
+            warnings.push(LoadError {
+                message: format!("Skipped the line, {}", problem.message),
+                ..problem
+            });
+            continue;
+        }
+
+        let read_symbol = parse_symbol(parts[1])
+            .ok_or_else(|| error(line_number, format!("Invalid read symbol '{}'", parts[1])))?;
+        let write_symbol = parse_symbol(parts[2])
+            .ok_or_else(|| error(line_number, format!("Invalid write symbol '{}'", parts[2])))?;
+        let action = match parts[3] {
+            "S" => Action::Stay,
+            "L" => Action::Left,
+            "R" => Action::Right,
+            action => {
+                return Err(error(
+                    line_number,
+                    format!("Invalid action '{action}', expected L, R or S"),
+                ))
+            }
+        };
+
+        let current_state = state_number(parts[0]);
+        // A state belongs to the instruction where it first has a transition
+        if state_instructions.len() <= current_state as usize {
+            state_instructions.resize(current_state as usize + 1, None);
+        }
+        if state_instructions[current_state as usize].is_none() {
+            state_instructions[current_state as usize] = assembly.len().checked_sub(1);
+        }
+        let instruction = Instruction {
+            write_symbol,
+            action,
+            next_state: state_number(parts[4]),
+        };
+
+        let rows = (current_state.max(instruction.next_state) as usize + 1) << 8;
+        if instructions.len() < rows {
+            instructions.resize(rows, Default::default());
+        }
+
+        let index = ((current_state << 8) | read_symbol as u32) as usize;
+        if let Some(first_line) = defined_on.insert(index, line_number) {
+            if instructions[index].as_ref() != Some(&instruction) {
+                let problem = error(
+                    line_number,
+                    format!(
+                        "State {} already has a different transition for symbol {} on line {}",
+                        parts[0], parts[1], first_line
+                    ),
+                );
+                if options.strict {
+                    return Err(problem);
+                }
+                warnings.push(problem);
+            }
+        }
+        instructions[index] = Some(instruction);
+    }
+
+    if state_names.is_empty() {
+        return Err(error(
+            code.lines().count(),
+            "The program has no transitions",
+        ));
+    }
+
+    state_instructions.resize(state_names.len(), None);
+
+    Ok(Program {
+        tape: Tape::new(tape),
+        head: head as i64,
+        instructions,
+        state_names,
+        assembly,
+        state_instructions,
+        warnings,
+    })
+}
+
+// The cells of the tape, the head starts on the cell marked with ! or on the first cell
+fn parse_tape(line: &str) -> Result<(Vec<u8>, usize), LoadError> {
+    let mut head = 0;
+    let mut tape = Vec::new();
+
+    for (i, cell) in line.split_whitespace().enumerate() {
+        let symbol = match cell.strip_prefix('!') {
+            Some(symbol) => {
+                head = i;
+                symbol
+            }
+            None => cell,
+        };
+        let symbol = parse_symbol(symbol)
+            .ok_or_else(|| error(1, format!("Invalid tape symbol '{cell}'")))?;
+        tape.push(symbol);
+    }
+
+    if tape.is_empty() {
+        return Err(error(1, "Missing the tape"));
+    }
+    Ok((tape, head))
+}
+
+// A symbol is a number from 0 to 255, or _ for the blank symbol 255
+fn parse_symbol(symbol: &str) -> Option<u8> {
+    match symbol {
+        "_" => Some(BLANK),
+        _ => symbol.parse().ok(),
+    }
+}
+
+fn error(line: usize, message: impl Into<String>) -> LoadError {
+    LoadError {
+        line,
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LoadOptions, Machine};
+
+    #[test]
+    fn load_turing_program() {
+        let program = "!0 1
+# comment
+A 0 1 R B
+A 0 0 R B
+A 0 1 R B
+broken line
+B 1 1 S END";
+        let machine = Machine::load(program).unwrap();
+        let warnings: Vec<String> = machine.warnings().iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            [
+                "line 4: State A already has a different transition for symbol 0 on line 3",
+                "line 5: State A already has a different transition for symbol 0 on line 4",
+                "line 6: Skipped the line, Expected a transition with 5 fields, but got 2 fields",
+            ]
+        );
+
+        let strict = LoadOptions { strict: true };
+        let error = Machine::load_with_options(program, &strict).unwrap_err();
+        assert_eq!(error.line, 4);
+
+        let error = Machine::load("!0 x\nA 0 1 R B").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: Invalid tape symbol 'x'");
+        let error = Machine::load("!0\nA 0 1 R B\nA 1 256 R B").unwrap_err();
+        assert_eq!(error.to_string(), "line 3: Invalid write symbol '256'");
+        let error = Machine::load("!0\nA 0 1 U B").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 2: Invalid action 'U', expected L, R or S"
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use std::{fmt, time::Instant};
+
+use serde::Serialize;
+
+use crate::{
+    history::Change,
+    load::{parse, LoadError, LoadOptions},
+    tape::Tape,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Action {
+    #[default]
+    Stay,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Instruction {
+    pub write_symbol: u8,
+    pub action: Action,
+    pub next_state: u32,
+}
+
+// Steps between two checks of the deadline in run_with_limit
+pub(crate) const DEADLINE_INTERVAL: u64 = 1 << 12;
+
+// Why the machine stopped running
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum RunOutcome {
+    Halted,
+    StepLimit,
+    Timeout,
+    Breakpoint(usize), // the index of the breakpoint in run_until
+    NoTransition {
+        state: String,
+        symbol: u8,
+        head: i64,
+    },
+}
+
+impl fmt::Display for RunOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunOutcome::Halted => write!(f, "halted"),
+            RunOutcome::StepLimit => write!(f, "stopped at the step limit"),
+            RunOutcome::Timeout => write!(f, "stopped at the deadline"),
+            RunOutcome::Breakpoint(index) => write!(f, "stopped at breakpoint {index}"),
+            RunOutcome::NoTransition {
+                state,
+                symbol,
+                head,
+            } => write!(
+                f,
+                "no transition for state {state} and symbol {symbol} at cell {head}"
+            ),
+        }
+    }
+}
+
+// A Turing machine loaded from a program in the format of the compiler.
+// The first line is the tape, the head starts on the cell marked with !.
+// Every other line is a transition `STATE READ WRITE ACTION NEXT_STATE`, lines starting with # are comments.
+// States are numbered in the order they first appear, the machine halts in the last new state.
+#[derive(Debug, Clone)]
+pub struct Machine {
+    pub(crate) state: u32,
+    pub(crate) head: i64,
+    pub(crate) tape: Tape,
+    pub(crate) instructions: Vec<Option<Instruction>>, // indexed by (state << 8) | symbol
+    sweeps: Vec<Option<Action>>, // the direction of pass-through transitions, indexed like instructions
+    pub(crate) state_names: Vec<String>,
+    pub(crate) assembly: Vec<String>, // the #asm headers of the program
+    pub(crate) state_instructions: Vec<Option<usize>>, // the index in assembly of every state
+    pub(crate) steps: u64,
+    warnings: Vec<LoadError>, // lines that were skipped or overwrote a transition
+    pub(crate) history: Option<Vec<Change>>, // the undo log of every step, when it is recorded
+}
+
+impl Machine {
+    pub fn load(code: &str) -> Result<Self, LoadError> {
+        Self::load_with_options(code, &LoadOptions::default())
+    }
+
+    pub fn load_with_options(code: &str, options: &LoadOptions) -> Result<Self, LoadError> {
+        let program = parse(code, options)?;
+
+        // A transition passes over a cell when it keeps the state and the symbol, and moves the head
+        let sweeps = program
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(index, instruction)| {
+                let instruction = instruction.as_ref()?;
+                let passes = instruction.next_state as usize == index >> 8
+                    && instruction.write_symbol as usize == index & 0xff
+                    && instruction.action != Action::Stay;
+                passes.then_some(instruction.action)
+            })
+            .collect();
+
+        Ok(Self {
+            state: 0,
+            head: program.head,
+            tape: program.tape,
+            instructions: program.instructions,
+            sweeps,
+            state_names: program.state_names,
+            assembly: program.assembly,
+            state_instructions: program.state_instructions,
+            steps: 0,
+            warnings: program.warnings,
+            history: None,
+        })
+    }
+
+    // Do one transition, the tape grows with a blank when the head moves past either end.
+    // Cells keep their coordinates when the tape grows on the left.
+    // Returns why the machine stopped if it can not do a transition, the machine is not changed then.
+    pub fn step(&mut self) -> Option<RunOutcome> {
+        if self.is_halted() {
+            return Some(RunOutcome::Halted);
+        }
+
+        let symbol = self.tape.get(self.head);
+        let Some(Some(instruction)) = self
+            .instructions
+            .get(((self.state << 8) | symbol as u32) as usize)
+        else {
+            return Some(RunOutcome::NoTransition {
+                state: self.state_name().to_string(),
+                symbol,
+                head: self.head,
+            });
+        };
+
+        let change = Change {
+            head: self.head,
+            state: self.state,
+            symbol,
+            grew: false,
+        };
+        self.tape.set(self.head, instruction.write_symbol);
+        match instruction.action {
+            Action::Stay => (),
+            Action::Left => self.head -= 1,
+            Action::Right => self.head += 1,
+        }
+        let grew = self.tape.extend_to(self.head);
+        if let Some(history) = &mut self.history {
+            history.push(Change { grew, ..change });
+        }
+        self.state = instruction.next_state;
+        self.steps += 1;
+        None
+    }
+
+    // Step until the machine halts or gets stuck
+    pub fn run(&mut self) -> RunOutcome {
+        self.run_with_limit(u64::MAX, None)
+    }
+
+    // Move the head over the run of cells that the state passes without changing them, in one jump.
+    // Stops before max_steps steps, at the first cell with another transition and at the ends of the tape.
+    // Returns the number of steps that were skipped, they are counted like single steps.
+    // Nothing is skipped while the history is recorded.
+    pub fn sweep(&mut self, max_steps: u64) -> u64 {
+        if self.is_halted() || self.history.is_some() {
+            return 0;
+        }
+
+        let row = (self.state as usize) << 8;
+        let sweeps = &self.sweeps[..];
+        let pass = |symbol: u8| sweeps.get(row | symbol as usize).copied().flatten();
+        let Some(action) = pass(self.tape.get(self.head)) else {
+            return 0;
+        };
+        let direction = if action == Action::Left { -1 } else { 1 };
+
+        let mut skipped = 0;
+        while skipped < max_steps {
+            let next = self.head + direction;
+            if next < self.tape.start() || next >= self.tape.end() {
+                break;
+            }
+            self.head = next;
+            skipped += 1;
+            if pass(self.tape.get(next)) != Some(action) {
+                break;
+            }
+        }
+        self.steps += skipped;
+        skipped
+    }
+
+    // Step until the machine halts or gets stuck, for at most max_steps steps and until the deadline.
+    // Runs of cells that a state passes over are skipped with sweep.
+    // The deadline is only checked every few thousand steps.
+    pub fn run_with_limit(&mut self, max_steps: u64, deadline: Option<Instant>) -> RunOutcome {
+        let mut steps = 0;
+        let mut round = 0;
+        while steps < max_steps {
+            steps += self.sweep(max_steps - steps);
+            if steps == max_steps {
+                break;
+            }
+            if let Some(outcome) = self.step() {
+                return outcome;
+            }
+            steps += 1;
+
+            if round % DEADLINE_INTERVAL == 0
+                && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return RunOutcome::Timeout;
+            }
+            round += 1;
+        }
+
+        if self.is_halted() {
+            RunOutcome::Halted
+        } else {
+            RunOutcome::StepLimit
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.state as usize + 1 == self.state_names.len()
+    }
+
+    pub fn tape(&self) -> &Tape {
+        &self.tape
+    }
+
+    // The coordinate of the cell under the head
+    pub fn head(&self) -> i64 {
+        self.head
+    }
+
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
+    pub fn state_name(&self) -> &str {
+        &self.state_names[self.state as usize]
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn warnings(&self) -> &[LoadError] {
+        &self.warnings
+    }
+}
+
+// The state and the tape, with the cell under the head in brackets
+impl fmt::Display for Machine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "state: {:<33}tape: ", self.state_name())?;
+        for (position, symbol) in (self.tape.start()..).zip(self.tape.iter()) {
+            if position == self.head {
+                write!(f, "[{}]", symbol)?;
+            } else {
+                write!(f, " {} ", symbol)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::{Machine, RunOutcome};
+
+    #[test]
+    fn run_outcomes() {
+        let endless = "!0\nA 0 0 S B\nB 0 0 S A\nA 1 1 S END";
+        let mut machine = Machine::load(endless).unwrap();
+        assert_eq!(machine.run_with_limit(1000, None), RunOutcome::StepLimit);
+        assert_eq!(machine.steps(), 1000);
+        assert_eq!(
+            machine.run_with_limit(u64::MAX, Some(Instant::now())),
+            RunOutcome::Timeout
+        );
+
+        let mut machine = Machine::load("!0 1\nA 0 0 R A\nA 2 2 S END").unwrap();
+        let outcome = machine.run();
+        assert_eq!(
+            outcome.to_string(),
+            "no transition for state A and symbol 1 at cell 1"
+        );
+        assert_eq!(machine.step(), Some(outcome));
+
+        let mut machine = Machine::load("!0\nA 0 1 S END").unwrap();
+        assert_eq!(machine.run(), RunOutcome::Halted);
+        assert!(machine.is_halted());
+    }
+
+    #[test]
+    fn sweep_steps() {
+        // The sweep over 0s stops at the 2 and continues past the right end one step at a time
+        let mut machine =
+            Machine::load("!0 0 0 2\nA 0 0 R A\nA 2 2 R B\nB _ _ R B\nB 1 1 S END").unwrap();
+        assert_eq!(machine.sweep(u64::MAX), 3);
+        assert_eq!((machine.head(), machine.steps()), (3, 3));
+        assert_eq!(machine.run_with_limit(10, None), RunOutcome::StepLimit);
+        assert_eq!((machine.head(), machine.tape().end()), (13, 14));
+
+        // A sweep stops exactly at the step limit, and is not counted twice
+        let mut machine = Machine::load("!0 0 0 0 0 0 0 1\nA 0 0 R A\nA 1 1 S END").unwrap();
+        assert_eq!(machine.sweep(4), 4);
+        assert_eq!(machine.run_with_limit(2, None), RunOutcome::StepLimit);
+        assert_eq!((machine.head(), machine.steps()), (6, 6));
+        assert_eq!(machine.run(), RunOutcome::Halted);
+        assert_eq!(machine.steps(), 8);
+    }
+}
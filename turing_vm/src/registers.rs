@@ -0,0 +1,25 @@
+// Values on the tape of a program from the compiler, the working area is 2[A]3[B]4[C]10 followed by the output.
+
+// The bits of A, between the first 2 and the last 3
+pub fn register_a(tape: &[u8]) -> Option<&[u8]> {
+    let start_a = tape.iter().position(|&x| x == 2)? + 1;
+    let end_a = tape.iter().rposition(|&x| x == 3)?;
+    tape.get(start_a..end_a)
+}
+
+// The printed values after the end of C (10), every value ends with a 6
+pub fn output(tape: &[u8]) -> Vec<u64> {
+    let Some(end_c) = tape.iter().position(|&x| x == 10) else {
+        return Vec::new();
+    };
+
+    tape[end_c + 1..]
+        .split(|&x| x == 6)
+        .filter(|bits| !bits.is_empty() && bits.iter().all(|&x| x < 2))
+        .map(to_number)
+        .collect()
+}
+
+pub fn to_number(bits: &[u8]) -> u64 {
+    bits.iter().fold(0, |value, &bit| value << 1 | bit as u64)
+}
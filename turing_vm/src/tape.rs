@@ -0,0 +1,124 @@
+// The blank symbol, written as _ in a Turing program
+pub const BLANK: u8 = 255;
+
+// A tape that grows in both directions without moving its cells.
+// Cells have stable signed coordinates, the first cell of the initial tape is 0 and cells added on the left are negative.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tape {
+    right: Vec<u8>, // cells 0, 1, 2, ...
+    left: Vec<u8>,  // cells -1, -2, -3, ...
+}
+
+impl Tape {
+    pub fn new(cells: Vec<u8>) -> Tape {
+        Tape {
+            right: cells,
+            left: Vec::new(),
+        }
+    }
+
+    // A tape with the cells from coordinate start, which is at most 0
+    pub(crate) fn from_cells(cells: &[u8], start: i64) -> Tape {
+        let (left, right) = cells.split_at(-start as usize);
+        Tape {
+            right: right.to_vec(),
+            left: left.iter().rev().copied().collect(),
+        }
+    }
+
+    // The symbol in a cell, cells that were never reached are blank
+    pub fn get(&self, position: i64) -> u8 {
+        let cell = if position >= 0 {
+            self.right.get(position as usize)
+        } else {
+            self.left.get((-position - 1) as usize)
+        };
+        cell.copied().unwrap_or(BLANK)
+    }
+
+    // Write a symbol, the tape grows with blanks up to the cell
+    pub fn set(&mut self, position: i64, symbol: u8) {
+        let (cells, index) = if position >= 0 {
+            (&mut self.right, position as usize)
+        } else {
+            (&mut self.left, (-position - 1) as usize)
+        };
+        if index >= cells.len() {
+            cells.resize(index + 1, BLANK);
+        }
+        cells[index] = symbol;
+    }
+
+    // Coordinate of the leftmost cell
+    pub fn start(&self) -> i64 {
+        -(self.left.len() as i64)
+    }
+
+    // Coordinate after the rightmost cell
+    pub fn end(&self) -> i64 {
+        self.right.len() as i64
+    }
+
+    pub fn len(&self) -> usize {
+        self.left.len() + self.right.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The symbols from the leftmost to the rightmost cell
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        self.left.iter().rev().chain(self.right.iter()).copied()
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.iter().collect()
+    }
+
+    // Make sure the cell is on the tape, so the tape includes every cell the head has been on.
+    // Returns true when the tape grew.
+    pub(crate) fn extend_to(&mut self, position: i64) -> bool {
+        if position < self.start() {
+            self.left.resize(-position as usize, BLANK);
+        } else if position >= self.end() {
+            self.right.resize(position as usize + 1, BLANK);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    // Remove the cell at position when it is the leftmost or rightmost cell, to undo extend_to
+    pub(crate) fn remove_edge(&mut self, position: i64) {
+        if position == self.start() && !self.left.is_empty() {
+            self.left.pop();
+        } else if position == self.end() - 1 {
+            self.right.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Machine, RunOutcome, BLANK};
+
+    #[test]
+    fn tape_coordinates() {
+        // Walk two cells left of the start and write a 1, then come back and stop on the first cell
+        let program = "3 !4\nA 4 4 L B\nB 3 3 L C\nC _ 1 L D\nD _ 2 R E\nE 1 1 R F\nF 3 3 S END";
+        let mut machine = Machine::load(program).unwrap();
+        assert_eq!(machine.head(), 1);
+        assert_eq!(machine.run(), RunOutcome::Halted);
+
+        let tape = machine.tape();
+        assert_eq!(machine.head(), 0);
+        assert_eq!((tape.start(), tape.end()), (-2, 2));
+        assert_eq!(tape.to_vec(), [2, 1, 3, 4]);
+        assert_eq!((tape.get(-1), tape.get(0), tape.get(5)), (1, 3, BLANK));
+        assert_eq!(
+            machine.to_string().split("tape: ").nth(1),
+            Some(" 2  1 [3] 4 ")
+        );
+    }
+}
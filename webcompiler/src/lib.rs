@@ -13,23 +13,195 @@ pub struct CompileResult {
     pub optimized_tac: String,
     pub assembly: String,
     pub turing_program: String,
+    pub warnings: String,
+    result: turing_compiler::CompilationResult,
 }
 
 #[wasm_bindgen]
-pub fn compile(code: &str) -> CompileResult {
-    let result = turing_compiler::compile(code);
-    CompileResult {
-        tokens: result.tokens_string,
-        ast: result.ast_string,
-        tac: result.tac_string,
-        optimized_tac: result.optimized_tac_string,
-        assembly: result.assembly_string,
-        turing_program: result.turing_program,
+impl CompileResult {
+    // The tape coordinates [start, end] of a variable such as global::x or global::xs[2],
+    // for the watches of Machine.run_until
+    pub fn watch(&self, name: &str) -> Result<Vec<i64>, JsValue> {
+        match self.result.watch(name) {
+            Some(turing_vm::Breakpoint::Watch { start, end }) => Ok(vec![start, end]),
+            _ => Err(JsValue::from_str(&format!(
+                "Variable {name} is not in the program"
+            ))),
+        }
     }
 }
 
+#[wasm_bindgen]
+pub fn compile(code: &str) -> Result<CompileResult, JsValue> {
+    console_error_panic_hook::set_once();
+    let result = turing_compiler::try_compile(code).map_err(|diagnostics| {
+        JsValue::from_str(&turing_compiler::diagnostics_to_string(&diagnostics))
+    })?;
+    Ok(CompileResult {
+        tokens: result.tokens_string.clone(),
+        ast: result.ast_string.clone(),
+        tac: result.tac_string.clone(),
+        optimized_tac: result.optimized_tac_string.clone(),
+        assembly: result.assembly_string.clone(),
+        turing_program: result.turing_program.clone(),
+        warnings: turing_compiler::diagnostics_to_string(&result.diagnostics),
+        result,
+    })
+}
+
 #[wasm_bindgen]
 pub fn lexer(code: &str) -> String {
     console_error_panic_hook::set_once();
     turing_compiler::lexer_json(code.to_string())
 }
+
+// A Turing machine running a compiled program, for stepping through it in the browser
+#[wasm_bindgen]
+pub struct Machine {
+    machine: turing_vm::Machine,
+}
+
+#[wasm_bindgen]
+impl Machine {
+    #[wasm_bindgen(constructor)]
+    pub fn new(turing_program: &str) -> Result<Machine, JsValue> {
+        console_error_panic_hook::set_once();
+        let machine = turing_vm::Machine::load(turing_program)
+            .map_err(|error| JsValue::from_str(&error.to_string()))?;
+        Ok(Machine { machine })
+    }
+
+    // Lines of the program that were skipped or overwrote a transition
+    pub fn warnings(&self) -> Vec<String> {
+        self.machine
+            .warnings()
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect()
+    }
+
+    // Do one transition, returns why the machine stopped if it could not
+    pub fn step(&mut self) -> Option<String> {
+        self.machine.step().map(|outcome| outcome.to_string())
+    }
+
+    // Run for at most max_steps steps, returns why the machine stopped.
+    // There is no deadline, since the browser has no clock for std::time::Instant.
+    pub fn run(&mut self, max_steps: u64) -> String {
+        self.machine.run_with_limit(max_steps, None).to_string()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.machine.is_halted()
+    }
+
+    pub fn tape(&self) -> Vec<u8> {
+        self.machine.tape().to_vec()
+    }
+
+    // The coordinate of the first cell in tape(), cells added on the left are negative
+    pub fn tape_start(&self) -> i64 {
+        self.machine.tape().start()
+    }
+
+    // The coordinate of the cell under the head, tape()[head - tape_start] is the current symbol
+    pub fn head(&self) -> i64 {
+        self.machine.head()
+    }
+
+    pub fn state(&self) -> String {
+        self.machine.state_name().to_string()
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.machine.steps()
+    }
+
+    // The assembly instructions of the program, from its #asm headers
+    pub fn assembly(&self) -> Vec<String> {
+        self.machine.assembly().to_vec()
+    }
+
+    // The index in assembly() of the instruction that is running
+    pub fn instruction(&self) -> Option<usize> {
+        self.machine.instruction()
+    }
+
+    // Run until a breakpoint is reached, for at most max_steps steps, returns why the machine stopped.
+    // The breakpoints are the starts of assembly instructions, labels, state names,
+    // and watches as pairs of coordinates from CompileResult.watch, flattened into one array.
+    // They are numbered in that order in "stopped at breakpoint {index}".
+    pub fn run_until(
+        &mut self,
+        instructions: Vec<usize>,
+        labels: Vec<String>,
+        states: Vec<String>,
+        watches: Vec<i64>,
+        max_steps: u64,
+    ) -> Result<String, JsValue> {
+        if watches.len() % 2 != 0 {
+            return Err(JsValue::from_str(
+                "Watches must be pairs of a start and an end coordinate",
+            ));
+        }
+
+        let breakpoints: Vec<turing_vm::Breakpoint> = instructions
+            .into_iter()
+            .map(turing_vm::Breakpoint::Instruction)
+            .chain(labels.into_iter().map(turing_vm::Breakpoint::Label))
+            .chain(states.into_iter().map(turing_vm::Breakpoint::State))
+            .chain(watches.chunks(2).map(|cells| turing_vm::Breakpoint::Watch {
+                start: cells[0],
+                end: cells[1],
+            }))
+            .collect();
+        self.machine
+            .run_until(&breakpoints, max_steps)
+            .map(|outcome| outcome.to_string())
+            .map_err(|error| JsValue::from_str(&error))
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            snapshot: self.machine.snapshot(),
+        }
+    }
+
+    // Go back to a snapshot of this machine, the recorded history is cleared
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.machine.restore(&snapshot.snapshot);
+    }
+
+    // Record every step so they can be undone, the debugger turns this on before stepping
+    pub fn record_history(&mut self, record: bool) {
+        self.machine.record_history(record);
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.machine.history_len()
+    }
+
+    // Undo the last recorded step, returns false when there is nothing to undo
+    pub fn step_back(&mut self) -> bool {
+        self.machine.step_back()
+    }
+
+    // Jump to a step number, backwards through the history or forwards by running.
+    // Returns false when the step could not be reached.
+    pub fn seek(&mut self, step: u64) -> bool {
+        self.machine.seek(step)
+    }
+}
+
+// The state of a Machine at one step, from Machine.snapshot()
+#[wasm_bindgen]
+pub struct Snapshot {
+    snapshot: turing_vm::Snapshot,
+}
+
+#[wasm_bindgen]
+impl Snapshot {
+    pub fn steps(&self) -> u64 {
+        self.snapshot.steps()
+    }
+}